@@ -1,14 +1,100 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use glib::{clone, subclass::prelude::*};
 use gtk::prelude::*;
-use gtk::{self, gio, glib};
+use gtk::{self, gdk, gio, glib};
+
+use crate::clipboard;
+use crate::file_operation::{self, Message, Pause};
+use crate::progress_info_model::{JobState, ProgressInfoModel};
+use crate::trash;
+use crate::utilities;
+use crate::undo_stack::Operation;
+use crate::window::Window;
 
-use crate::progress_info_model::ProgressInfoModel;
+/// Jobs running at once, on top of the one-job-per-device rule.
+const MAX_RUNNING_JOBS: usize = 3;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ProcessType {
+    #[default]
     Copy,
     Move,
 }
 
+impl ProcessType {
+    fn verb(self) -> &'static str {
+        match self {
+            ProcessType::Copy => "Copying",
+            ProcessType::Move => "Moving",
+        }
+    }
+}
+
+/// Renames each `(before, after)` pair, both sides living in the same folder, and
+/// returns the pairs that went through.
+///
+/// Names may be swapped within one batch, so with more than one file everything
+/// goes through a temporary name first.
+fn rename_all(pairs: &[(gio::File, gio::File)]) -> Vec<(gio::File, gio::File)> {
+    let set_name = |file: &gio::File, to: &gio::File| {
+        let name = to.basename().expect("Error getting file name");
+        file.set_display_name(&name.to_string_lossy(), None::<&gio::Cancellable>)
+    };
+
+    let pairs: Vec<&(gio::File, gio::File)> =
+        pairs.iter().filter(|(before, after)| !before.equal(after)).collect();
+    if let [(before, after)] = pairs.as_slice() {
+        return set_name(before, after)
+            .map(|_| vec![(before.clone(), after.clone())])
+            .unwrap_or_default();
+    }
+
+    let parked: Vec<(&(gio::File, gio::File), gio::File)> = pairs
+        .into_iter()
+        .filter_map(|pair| {
+            let dir = pair.0.parent()?;
+            let name = pair.0.basename()?;
+            let temporary = file_operation::unique_child(
+                &dir,
+                &format!(".{}.renaming", name.to_string_lossy()),
+            );
+            set_name(&pair.0, &temporary).ok().map(|parked| (pair, parked))
+        })
+        .collect();
+
+    parked
+        .into_iter()
+        .filter_map(|((before, after), parked)| match set_name(&parked, after) {
+            Ok(_) => Some((before.clone(), after.clone())),
+            Err(_) => {
+                let _ = set_name(&parked, before);
+                None
+            }
+        })
+        .collect()
+}
+
+/// How undoing or redoing an operation goes, see `Application::toast_outcome`.
+enum Outcome {
+    /// Done right away, with the number of items that failed.
+    Done(usize),
+    /// Left to a job.
+    Job(ProgressInfoModel),
+    /// Files still to go to the trash, one after the other in the background.
+    Trash(Vec<gio::File>),
+}
+
+/// What a transfer of `items` touches, the sources and the folders they go to.
+fn transfer_files(items: &[(gio::File, gio::File)]) -> Vec<gio::File> {
+    items
+        .iter()
+        .flat_map(|(src, target)| [src.clone(), target.parent().unwrap_or_else(|| target.clone())])
+        .collect()
+}
+
 glib::wrapper! {
     pub struct Application(ObjectSubclass<imp::Application>)
         @extends gio::Application, gtk::Application, @implements gio::ActionGroup, gio::ActionMap;
@@ -29,60 +115,609 @@ impl Application {
         .expect("Failed to create Application")
     }
 
-    pub fn do_sync(&self, _action: ProcessType, dest: &gio::File) {
-        let store = &self.imp().0 .0;
+    pub fn do_sync(&self, action: ProcessType, sources: Vec<gio::File>, dest: &gio::File) {
+        // A folder would keep finding its own copy inside itself and never be done.
+        if sources.iter().any(|src| dest.equal(src) || dest.has_prefix(src)) {
+            self.toast("A folder can't be copied or moved into itself");
+            return;
+        }
 
-        let model: ProgressInfoModel = glib::Object::new(&[(
-            "msg",
+        let sources_len = sources.len();
+
+        let items: Vec<(gio::File, gio::File)> = sources
+            .into_iter()
+            .filter_map(|src| {
+                let target = dest.child(src.basename()?);
+                Some((src, target))
+            })
+            .collect();
+
+        let model = self.transfer(
+            action,
+            items,
             &format!(
-                "Downloading 3 items to {}",
-                dest.basename().unwrap().to_str().unwrap()
+                "{} {} items to {}",
+                action.verb(),
+                sources_len,
+                dest.basename()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
             ),
-        )])
-        .expect("Failed to create ProgressInfoModel");
+        );
+
+        // Record what actually happened, cancelled jobs included.
+        self.when_done(&model, move |app, items| {
+            if !items.is_empty() {
+                app.record(match action {
+                    ProcessType::Copy => Operation::Copy(items),
+                    ProcessType::Move => Operation::Move(items),
+                });
+            }
+        });
+    }
+
+    /// Copies each of `files` next to itself, named as in `name (copy)`.
+    pub fn duplicate(&self, files: &[gio::File]) {
+        let msg = format!("Duplicating {} items", files.len());
+        let sources = files.to_vec();
+        let model = self.job(&msg, files, move |cancellable, pause, sender| {
+            // Names are picked once the job runs, an earlier duplicate may have taken one.
+            let items: Vec<(gio::File, gio::File)> = sources
+                .iter()
+                .filter_map(|file| {
+                    let name = file.basename()?.to_string_lossy().into_owned();
+                    let copy = file_operation::duplicate_child(&file.parent()?, &name);
+                    Some((file.clone(), copy))
+                })
+                .collect();
+            file_operation::transfer(ProcessType::Copy, &items, cancellable, pause, sender);
+        });
+
+        self.when_done(&model, |app, items| {
+            if !items.is_empty() {
+                app.record(Operation::Copy(items));
+            }
+        });
+    }
+
+    /// Makes a symbolic link to each of `files` in `dest`, or in the folder the file lives in.
+    ///
+    /// Relative links keep working when the folder holding both is moved elsewhere.
+    pub fn make_links(&self, files: &[gio::File], dest: Option<&gio::File>, relative: bool) {
+        let mut links = Vec::new();
+        let mut failed = 0;
+
+        for file in files {
+            let dir = dest.cloned().or_else(|| file.parent());
+            let (dir, path, name) = match (dir, file.path(), file.basename()) {
+                (Some(dir), Some(path), Some(name)) => (dir, path, name),
+                _ => {
+                    failed += 1;
+                    continue;
+                }
+            };
+            let target = match dir.path() {
+                Some(dir) if relative => utilities::relative_path(&dir, &path),
+                _ => path,
+            };
+
+            // Only a link sitting next to its target needs a name of its own.
+            let name = name.to_string_lossy();
+            let link = match dest {
+                Some(_) => file_operation::available_child(&dir, &name),
+                None => file_operation::available_child(&dir, &format!("Link to {}", name)),
+            };
+            match link.make_symbolic_link(&target, None::<&gio::Cancellable>) {
+                Ok(()) => links.push((target, link)),
+                Err(_) => failed += 1,
+            }
+        }
+
+        if failed > 0 {
+            self.toast(&format!("{} links could not be made", failed));
+        }
+        if !links.is_empty() {
+            self.record(Operation::Link(links));
+        }
+    }
+
+    /// Creates a folder in `dir` and moves `files` into it, undone as one step.
+    ///
+    /// `then` is called with the folder once the move went through and is recorded, the
+    /// targets of the queued move only stay valid while the folder keeps its name.
+    pub fn new_folder_with(
+        &self,
+        dir: &gio::File,
+        files: &[gio::File],
+        then: impl Fn(&gio::File) + 'static,
+    ) {
+        let folder = file_operation::available_child(dir, "New Folder");
+        if let Err(err) = folder.make_directory(None::<&gio::Cancellable>) {
+            self.toast(err.message());
+            return;
+        }
+
+        let items = files
+            .iter()
+            .filter_map(|file| Some((file.clone(), folder.child(file.basename()?))))
+            .collect();
+        let model = self.transfer(
+            ProcessType::Move,
+            items,
+            &format!("Moving {} items to a new folder", files.len()),
+        );
+
+        self.when_done(&model, clone!(@strong folder, @weak model => move |app, items| {
+            if model.state() == JobState::Finished {
+                app.record(Operation::CreateFolderWith(folder.clone(), items));
+                if folder.query_exists(None::<&gio::Cancellable>) {
+                    then(&folder);
+                }
+            } else if !items.is_empty() {
+                // What made it into the folder can still be moved back, the folder stays.
+                app.record(Operation::Move(items));
+            }
+        }));
+    }
+
+    /// Calls `f` with the items `model` completed once it is over, whatever the outcome.
+    fn when_done(
+        &self,
+        model: &ProgressInfoModel,
+        f: impl Fn(&Self, Vec<(gio::File, gio::File)>) + 'static,
+    ) {
+        // A job is over once, the handler goes away when it is.
+        let handler = Rc::new(RefCell::new(None));
+        let id = model.connect_notify_local(
+            Some("state"),
+            clone!(@weak self as app, @strong handler => move |model, _| {
+                if !model.is_active() {
+                    if let Some(id) = handler.take() {
+                        model.disconnect(id);
+                    }
+                    f(&app, model.take_completed());
+                }
+            }),
+        );
+        handler.replace(Some(id));
+    }
+
+    /// Queues a copy or move of each source in `items` to the target paired with it.
+    fn transfer(
+        &self,
+        action: ProcessType,
+        items: Vec<(gio::File, gio::File)>,
+        msg: &str,
+    ) -> ProgressInfoModel {
+        self.job(msg, &transfer_files(&items), move |cancellable, pause, sender| {
+            file_operation::transfer(action, &items, cancellable, pause, sender);
+        })
+    }
+
+    /// Queues a move of each of `items`, as `(from, to)`, back where it came from.
+    ///
+    /// A move merged into an existing folder removed the folders it emptied, those are
+    /// made again first.
+    fn move_back(&self, items: &[(gio::File, gio::File)], msg: &str) -> ProgressInfoModel {
+        let items: Vec<(gio::File, gio::File)> = items
+            .iter()
+            .map(|(from, to)| (to.clone(), from.clone()))
+            .collect();
+
+        self.job(msg, &transfer_files(&items), move |cancellable, pause, sender| {
+            for dir in items.iter().filter_map(|(_, target)| target.parent()) {
+                // Fails with `Exists` for every folder that is still there.
+                let _ = dir.make_directory_with_parents(Some(cancellable));
+            }
+            file_operation::transfer(ProcessType::Move, &items, cancellable, pause, sender);
+        })
+    }
+
+    /// Queues a job deleting `files` for good.
+    pub fn delete(&self, files: Vec<gio::File>) {
+        let msg = format!("Deleting {} items", files.len());
+        let devices = files.clone();
+        self.job(&msg, &devices, move |cancellable, pause, sender| {
+            file_operation::delete(&files, cancellable, pause, sender);
+        });
+    }
+
+    /// Queues a job deleting everything in every trash can.
+    pub fn empty_trash(&self) {
+        self.job("Emptying the trash", &[trash::location()], |cancellable, pause, sender| {
+            file_operation::delete(&trash::items(), cancellable, pause, sender);
+        });
+    }
+
+    /// Queues `work` as a job on the devices of `files`.
+    fn job(
+        &self,
+        msg: &str,
+        files: &[gio::File],
+        work: impl FnOnce(&gio::Cancellable, &Pause, &glib::Sender<Message>) + Send + 'static,
+    ) -> ProgressInfoModel {
+        let model: ProgressInfoModel = glib::Object::new(&[("msg", &msg)])
+            .expect("Failed to create ProgressInfoModel");
 
         let sender = model.imp().sender.clone();
+        let cancellable = model.imp().cancellable.clone();
+        let pause = model.imp().pause.clone();
 
-        // dbg!(self.imp().store.borrow().n_items());
+        model.set_job(move || {
+            work(&cancellable, &pause, &sender);
+        });
+        self.queue(&model);
 
-        let total_size = store.snapshot().iter().fold(0i64, |mut sum, val| {
-            val.downcast_ref::<gio::FileInfo>().map_or(sum, |v| {
-                sum += v.size();
-                sum
-            })
+        // Looking devices up may block on a slow mount, so it happens on a thread of its own.
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let files = files.to_vec();
+        std::thread::spawn(move || {
+            let _ = sender.send(file_operation::devices(&files));
         });
+        receiver.attach(
+            None,
+            clone!(@weak self as app, @weak model => @default-return glib::Continue(false), move |devices| {
+                model.set_devices(devices);
+                app.schedule();
+                glib::Continue(false)
+            }),
+        );
+
+        model
+    }
 
-        for item in store
+    /// Adds a job to `processes-store`, it starts as soon as the devices it touches are free.
+    pub fn queue(&self, model: &ProgressInfoModel) {
+        model.connect_notify_local(
+            Some("state"),
+            clone!(@weak self as app => move |_, _| app.schedule()),
+        );
+        self.imp().0 .1.append(model);
+        self.schedule();
+    }
+
+    /// Starts queued jobs on the next idle, so state changes never re-enter the queue.
+    fn schedule(&self) {
+        glib::idle_add_local_once(clone!(@weak self as app => move || app.run_queued_jobs()));
+    }
+
+    /// Starts queued jobs in store order, one per device and `MAX_RUNNING_JOBS` overall.
+    fn run_queued_jobs(&self) {
+        let jobs: Vec<ProgressInfoModel> = self.imp().0 .1
             .snapshot()
+            .into_iter()
+            .filter_map(|o| o.downcast::<ProgressInfoModel>().ok())
+            .collect();
+
+        // Paused jobs keep their devices, they pick up where they stopped once resumed.
+        let running: Vec<&ProgressInfoModel> = jobs
             .iter()
-            .filter_map(glib::Object::downcast_ref::<gio::FileInfo>)
-        {
-            let name = item.display_name();
-            let dest = dest.child(&*name);
+            .filter(|job| matches!(job.state(), JobState::Running | JobState::Paused))
+            .collect();
+        let mut busy: HashSet<String> = running
+            .iter()
+            .flat_map(|job| job.devices().unwrap_or_default())
+            .collect();
+        let mut n_running = running.len();
 
-            if let Some(src) = item
-                .attribute_object("standard::file")
-                .and_then(|o| o.downcast::<gio::File>().ok())
-            {
-                src.copy_async(
-                    &dest,
-                    gio::FileCopyFlags::OVERWRITE,
-                    glib::PRIORITY_LOW,
-                    Some(&model.imp().cancellable),
-                    Some(Box::from(clone!(@strong sender, @strong total_size => move |x: i64, _| {
-                        //sender.send(Ok::<i64, glib::Error>(x)).expect("Error sending value");
-                        sender.send(x as f64 / total_size as f64).expect("Error sending value");
-                    }))),
-                    clone!(@strong sender => move |ret| {
-                        sender.send(if ret.is_ok() { 1.0 } else { -1.0 }).expect("Error sending value");
-                    }),
-                );
+        for job in jobs.iter().filter(|job| job.state() == JobState::Queued) {
+            if n_running >= MAX_RUNNING_JOBS {
+                break;
             }
+            let devices = match job.devices() {
+                Some(devices) => devices,
+                None => continue,
+            };
+            if devices.iter().any(|device| busy.contains(device)) {
+                continue;
+            }
+            busy.extend(devices);
+            n_running += 1;
+            job.start();
         }
+    }
 
-        self.imp().0 .1.append(&model);
+    /// Moves a queued job one place closer to the front of the queue.
+    pub fn move_job_up(&self, model: &ProgressInfoModel) {
+        let store = &self.imp().0 .1;
+        if let Some(pos) = (1..store.n_items()).find(|&pos| {
+            store
+                .item(pos)
+                .map_or(false, |item| &item == model.upcast_ref::<glib::Object>())
+        }) {
+            store.remove(pos);
+            store.insert(pos - 1, model);
+        }
+    }
+
+    /// Puts `files` on the clipboard, to be copied or moved wherever they are pasted.
+    pub fn set_selected_items(&self, action: ProcessType, files: &[gio::File]) {
+        // Set first, views look at it as soon as the store changes.
+        self.imp().1.set(action);
+        let store = &self.imp().0 .0;
+        store.remove_all();
+        for file in files {
+            store.append(file);
+        }
+
+        if let Some(clipboard) = clipboard::clipboard() {
+            clipboard::set(&clipboard, action, files);
+        }
+    }
+
+    /// Whether `file` was cut here and waits to be pasted.
+    pub fn is_cut(&self, file: &gio::File) -> bool {
+        let imp = self.imp();
+        imp.1.get() == ProcessType::Move
+            && imp.0 .0
+                .snapshot()
+                .iter()
+                .filter_map(|item| item.downcast_ref::<gio::File>())
+                .any(|cut| cut.equal(file))
+    }
 
-        dbg!(self.imp().0 .1.n_items());
+    /// Forgets about cut items, they stay where they are.
+    pub fn cancel_cut(&self) {
+        if self.imp().1.get() != ProcessType::Move || self.imp().0 .0.n_items() == 0 {
+            return;
+        }
+        self.imp().0 .0.remove_all();
+        if let Some(clipboard) = clipboard::clipboard().filter(|c| c.is_local()) {
+            let _ = clipboard.set_content(None::<&gdk::ContentProvider>);
+        }
+    }
+
+    /// Copies or moves the files on the clipboard to `dest`, whichever app put them there.
+    pub fn paste(&self, dest: &gio::File) {
+        let clipboard = match clipboard::clipboard() {
+            Some(clipboard) => clipboard,
+            None => return,
+        };
+
+        glib::MainContext::default().spawn_local(clone!(@weak self as app, @strong dest => async move {
+            let (action, files) = match clipboard::read(&clipboard).await {
+                Some((action, files)) if !files.is_empty() => (action, files),
+                _ => return,
+            };
+
+            // Cut items are gone from their source once moved, so they can only be pasted once.
+            if action == ProcessType::Move {
+                app.imp().0 .0.remove_all();
+                let _ = clipboard.set_content(None::<&gdk::ContentProvider>);
+            }
+            app.do_sync(action, files, &dest);
+        }));
+    }
+
+    /// Moves `files` to the trash of the volume they live on, one after the other in
+    /// the background.
+    pub fn trash(&self, files: &[gio::File]) {
+        let files = files.to_vec();
+        glib::MainContext::default().spawn_local(clone!(@weak self as app => async move {
+            let mut trashed = Vec::new();
+            let mut failed = 0;
+            for file in files {
+                match file.trash_future(glib::PRIORITY_DEFAULT).await {
+                    Ok(()) => trashed.push(file),
+                    Err(_) => failed += 1,
+                }
+            }
+
+            if failed > 0 {
+                app.toast(&format!(
+                    "{} items could not be moved to the trash, Shift+Delete deletes them permanently",
+                    failed
+                ));
+            } else if !trashed.is_empty() {
+                app.toast(&format!("Moved {} items to the trash", trashed.len()));
+            }
+
+            if !trashed.is_empty() {
+                app.record(Operation::Trash(trashed));
+            }
+        }));
+    }
+
+    /// Queues a job putting trashed `items` back where they came from.
+    pub fn restore(&self, items: &[gio::File]) {
+        let msg = format!("Restoring {} items", items.len());
+        let mut files = items.to_vec();
+        files.push(trash::location());
+        let items = items.to_vec();
+        self.restore_job(&msg, &files, items.len(), move || {
+            items
+                .iter()
+                .filter_map(|item| Some((item.clone(), trash::original_location(item)?)))
+                .collect()
+        });
+    }
+
+    /// Queues a job moving trashed items back, `find` looks up `expected` of them as
+    /// `(item, original)` once the job runs, since that reads the whole trash.
+    fn restore_job(
+        &self,
+        msg: &str,
+        files: &[gio::File],
+        expected: usize,
+        find: impl FnOnce() -> Vec<(gio::File, gio::File)> + Send + 'static,
+    ) -> ProgressInfoModel {
+        self.job(msg, files, move |cancellable, pause, sender| {
+            let found = find();
+            if found.is_empty() {
+                let _ = sender.send(Message::Failed);
+                return;
+            }
+            if found.len() < expected {
+                let missing = expected - found.len();
+                let _ = sender.send(Message::Summary(format!("{} items were not found", missing)));
+            }
+            let items = trash::restore_targets(found, cancellable);
+            file_operation::transfer(ProcessType::Move, &items, cancellable, pause, sender);
+        })
+    }
+
+    /// Renames each `(before, after)` pair as one undoable step.
+    pub fn rename(&self, pairs: &[(gio::File, gio::File)]) {
+        let renamed = rename_all(pairs);
+        let failed = pairs.iter().filter(|(before, after)| !before.equal(after)).count()
+            - renamed.len();
+        if failed > 0 {
+            self.toast(&format!("{} items could not be renamed", failed));
+        }
+        if !renamed.is_empty() {
+            self.record(Operation::Rename(renamed));
+        }
+    }
+
+    /// Adds an operation to the undo history, jobs record themselves when they end.
+    pub fn record(&self, operation: Operation) {
+        self.imp().2.push(operation);
+    }
+
+    pub fn undo(&self) {
+        let operation = match self.imp().2.pop_undo() {
+            Some(operation) => operation,
+            None => return self.toast("Nothing to undo"),
+        };
+
+        let outcome = match &operation {
+            Operation::Copy(items) => {
+                Outcome::Trash(items.iter().map(|(_, copy)| copy.clone()).collect())
+            }
+            Operation::Move(items) => Outcome::Job(self.move_back(items, "Undoing move")),
+            Operation::CreateFolder(dir) => {
+                // Only an empty folder goes, anything put in it since is kept.
+                Outcome::Done(usize::from(dir.delete(None::<&gio::Cancellable>).is_err()))
+            }
+            Operation::CreateFile(file) => {
+                // It may have been written to since, so it goes to the trash.
+                Outcome::Trash(vec![file.clone()])
+            }
+            Operation::CreateFolderWith(folder, items) => {
+                let model = self.move_back(items, "Undoing move");
+                self.when_done(&model, clone!(@strong folder => move |_, _| {
+                    let _ = folder.delete(None::<&gio::Cancellable>);
+                }));
+                Outcome::Job(model)
+            }
+            Operation::Rename(pairs) => {
+                let pairs: Vec<(gio::File, gio::File)> = pairs
+                    .iter()
+                    .map(|(before, after)| (after.clone(), before.clone()))
+                    .collect();
+                Outcome::Done(pairs.len() - rename_all(&pairs).len())
+            }
+            Operation::Link(links) => Outcome::Done(
+                links
+                    .iter()
+                    .filter(|(_, link)| link.delete(None::<&gio::Cancellable>).is_err())
+                    .count(),
+            ),
+            Operation::Trash(files) => {
+                let mut devices: Vec<gio::File> =
+                    files.iter().filter_map(|file| file.parent()).collect();
+                devices.push(trash::location());
+                let originals = files.clone();
+                Outcome::Job(self.restore_job("Undoing trash", &devices, files.len(), move || {
+                    trash::find(&originals)
+                }))
+            }
+        };
+
+        self.toast_outcome(outcome, &operation, true);
+    }
+
+    pub fn redo(&self) {
+        let operation = match self.imp().2.pop_redo() {
+            Some(operation) => operation,
+            None => return self.toast("Nothing to redo"),
+        };
+
+        let outcome = match &operation {
+            Operation::Copy(items) => {
+                Outcome::Job(self.transfer(ProcessType::Copy, items.clone(), "Redoing copy"))
+            }
+            Operation::Move(items) => {
+                Outcome::Job(self.transfer(ProcessType::Move, items.clone(), "Redoing move"))
+            }
+            Operation::CreateFolder(dir) => {
+                Outcome::Done(usize::from(dir.make_directory(None::<&gio::Cancellable>).is_err()))
+            }
+            Operation::CreateFile(file) => {
+                let created = file.create(gio::FileCreateFlags::NONE, None::<&gio::Cancellable>);
+                Outcome::Done(usize::from(created.is_err()))
+            }
+            Operation::CreateFolderWith(folder, items) => {
+                match folder.make_directory(None::<&gio::Cancellable>) {
+                    Err(err) if !err.matches(gio::IOErrorEnum::Exists) => Outcome::Done(1),
+                    _ => Outcome::Job(self.transfer(
+                        ProcessType::Move,
+                        items.clone(),
+                        "Redoing move",
+                    )),
+                }
+            }
+            Operation::Rename(pairs) => Outcome::Done(pairs.len() - rename_all(pairs).len()),
+            Operation::Link(links) => Outcome::Done(
+                links
+                    .iter()
+                    .filter(|(target, link)| {
+                        link.make_symbolic_link(target, None::<&gio::Cancellable>).is_err()
+                    })
+                    .count(),
+            ),
+            Operation::Trash(files) => Outcome::Trash(files.clone()),
+        };
+
+        self.toast_outcome(outcome, &operation, false);
+    }
+
+    /// Tells how undoing or redoing `operation` went, once `outcome` is known.
+    fn toast_outcome(&self, outcome: Outcome, operation: &Operation, undo: bool) {
+        let (done, verb) = if undo { ("Undid", "undo") } else { ("Redid", "redo") };
+        let description = operation.description();
+        let result = move |failed: usize| {
+            if failed == 0 {
+                format!("{} {}", done, description)
+            } else {
+                format!("Could not {} {}", verb, description)
+            }
+        };
+
+        match outcome {
+            Outcome::Done(failed) => self.toast(&result(failed)),
+            Outcome::Trash(files) => {
+                glib::MainContext::default().spawn_local(clone!(@weak self as app => async move {
+                    let mut failed = 0;
+                    for file in files {
+                        if file.trash_future(glib::PRIORITY_DEFAULT).await.is_err() {
+                            failed += 1;
+                        }
+                    }
+                    app.toast(&result(failed));
+                }));
+            }
+            Outcome::Job(model) => {
+                let stopped = format!("Stopped the {} of {}", verb, operation.description());
+                self.when_done(&model, clone!(@weak model => move |app, _| {
+                    app.toast(&match model.state() {
+                        JobState::Finished => result(0),
+                        JobState::Cancelled => stopped.clone(),
+                        _ => result(1),
+                    });
+                }));
+            }
+        }
+    }
+
+    /// Shows `msg` in the window the user is looking at.
+    pub fn toast(&self, msg: &str) {
+        if let Some(window) = self
+            .active_window()
+            .and_then(|w| w.downcast::<Window>().ok())
+        {
+            window.add_toast(msg);
+        }
     }
 }
 
@@ -91,12 +726,19 @@ mod imp {
     use gtk::prelude::*;
     use gtk::subclass::prelude::*;
     use gtk::{self, gio, glib};
+    use std::cell::Cell;
 
+    use crate::application::ProcessType;
+    use crate::undo_stack::UndoStack;
     use crate::window::Window;
 
     #[derive(Debug, Default)]
     // By implementing Default we don't have to provide a `new` fn in our ObjectSubclass impl.
-    pub struct Application(pub (gio::ListStore, gio::ListStore));
+    pub struct Application(
+        pub (gio::ListStore, gio::ListStore),
+        pub Cell<ProcessType>,
+        pub UndoStack,
+    );
 
     #[glib::object_subclass]
     impl ObjectSubclass for Application {
@@ -210,8 +852,25 @@ mod imp {
                 app.quit();
             }));
 
+            let action_undo = gio::SimpleAction::new("undo", None);
+            action_undo.connect_activate(clone!(@strong app => move |_, _| app.undo()));
+
+            let action_redo = gio::SimpleAction::new("redo", None);
+            action_redo.connect_activate(clone!(@strong app => move |_, _| app.redo()));
+
             app.add_action(&action_quit);
             app.add_action(&action_about);
+            app.add_action(&action_undo);
+            app.add_action(&action_redo);
+
+            // Whatever was cut or copied here is forgotten once another app takes the clipboard.
+            if let Some(clipboard) = crate::clipboard::clipboard() {
+                clipboard.connect_changed(clone!(@weak app => move |clipboard| {
+                    if !clipboard.is_local() {
+                        app.imp().0 .0.remove_all();
+                    }
+                }));
+            }
 
             app.set_accels_for_action("app.quit", &["<Control>q"]);
         }
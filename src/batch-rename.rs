@@ -0,0 +1,500 @@
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{self, gio, glib};
+use regex::{NoExpand, Regex};
+
+use crate::application::Application;
+use crate::utilities;
+use crate::window::Window;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    Title,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    #[default]
+    Before,
+    After,
+}
+
+/// What a batch rename does to each name, applied in field order.
+#[derive(Debug, Default, Clone)]
+pub struct Rules {
+    pub find: String,
+    pub replace: String,
+    pub regex: bool,
+    pub case: Case,
+    /// First number, digits to pad it to and where it goes.
+    pub numbering: Option<(u32, usize, Position)>,
+    /// `strftime` like format of the modification date and where it goes.
+    pub date: Option<(String, Position)>,
+}
+
+impl Rules {
+    /// The find pattern, `None` when there is nothing to find.
+    fn pattern(&self) -> Result<Option<Regex>, regex::Error> {
+        if self.find.is_empty() {
+            return Ok(None);
+        }
+        if self.regex {
+            Regex::new(&self.find).map(Some)
+        } else {
+            Regex::new(&regex::escape(&self.find)).map(Some)
+        }
+    }
+
+    /// Name of the `index`th item of the batch, the extension is left as it is
+    /// by everything but find and replace.
+    fn apply(
+        &self,
+        pattern: Option<&Regex>,
+        name: &str,
+        index: usize,
+        modified: Option<&glib::DateTime>,
+    ) -> String {
+        let name = match pattern {
+            Some(pattern) if self.regex => pattern.replace_all(name, self.replace.as_str()),
+            Some(pattern) => pattern.replace_all(name, NoExpand(&self.replace)),
+            None => name.into(),
+        };
+
+        let (stem, extension) = utilities::split_extension(&name);
+
+        let mut stem = match self.case {
+            Case::Keep => stem.to_owned(),
+            Case::Lower => stem.to_lowercase(),
+            Case::Upper => stem.to_uppercase(),
+            Case::Title => title_case(stem),
+        };
+
+        let mut insert = |text: String, position: Position| {
+            stem = match position {
+                Position::Before => format!("{} {}", text, stem),
+                Position::After => format!("{} {}", stem, text),
+            };
+        };
+
+        if let Some((start, digits, position)) = self.numbering {
+            insert(
+                format!("{:0width$}", start as usize + index, width = digits),
+                position,
+            );
+        }
+        if let Some((format, position)) = &self.date {
+            if let Some(date) = modified.and_then(|date| date.format(format).ok()) {
+                insert(date.to_string(), *position);
+            }
+        }
+
+        format!("{}{}", stem, extension)
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut word_start = true;
+    text.chars()
+        .flat_map(|c| {
+            let upper = word_start;
+            word_start = c.is_whitespace() || c == '-' || c == '_';
+            if upper {
+                c.to_uppercase().collect::<Vec<char>>()
+            } else {
+                c.to_lowercase().collect()
+            }
+        })
+        .collect()
+}
+
+struct Item {
+    file: gio::File,
+    name: String,
+    modified: Option<glib::DateTime>,
+}
+
+/// New name of each item with what's wrong with it, if anything.
+fn preview(rules: &Rules, items: &[Item]) -> Result<Vec<(String, Option<&'static str>)>, String> {
+    let pattern = rules.pattern().map_err(|err| err.to_string())?;
+    let names: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| rules.apply(pattern.as_ref(), &item.name, index, item.modified.as_ref()))
+        .collect();
+
+    Ok(names
+        .iter()
+        .zip(items)
+        .map(|(name, item)| {
+            let error = utilities::name_error(name).or_else(|| {
+                let target = item.file.parent()?.child(name);
+                if names.iter().filter(|other| *other == name).count() > 1 {
+                    Some("Same name as another renamed item")
+                } else if target.query_exists(None::<&gio::Cancellable>)
+                    && !items.iter().any(|item| item.file.equal(&target))
+                {
+                    Some("An item with this name already exists")
+                } else {
+                    None
+                }
+            });
+            (name.clone(), error)
+        })
+        .collect())
+}
+
+/// The widgets of the batch rename dialog.
+struct Form {
+    find: gtk::Entry,
+    replace: gtk::Entry,
+    regex: gtk::CheckButton,
+    case: gtk::DropDown,
+    numbering: gtk::CheckButton,
+    start: gtk::SpinButton,
+    digits: gtk::SpinButton,
+    number_position: gtk::DropDown,
+    date: gtk::CheckButton,
+    date_format: gtk::Entry,
+    date_position: gtk::DropDown,
+    preview: gtk::ListBox,
+    error: gtk::Label,
+}
+
+impl Form {
+    fn rules(&self) -> Rules {
+        let position = |dropdown: &gtk::DropDown| match dropdown.selected() {
+            0 => Position::Before,
+            _ => Position::After,
+        };
+
+        Rules {
+            find: self.find.text().to_string(),
+            replace: self.replace.text().to_string(),
+            regex: self.regex.is_active(),
+            case: match self.case.selected() {
+                1 => Case::Lower,
+                2 => Case::Upper,
+                3 => Case::Title,
+                _ => Case::Keep,
+            },
+            numbering: self.numbering.is_active().then(|| {
+                (
+                    self.start.value_as_int() as u32,
+                    self.digits.value_as_int() as usize,
+                    position(&self.number_position),
+                )
+            }),
+            date: self
+                .date
+                .is_active()
+                .then(|| (self.date_format.text().to_string(), position(&self.date_position))),
+        }
+    }
+
+    /// Shows the names `rules` would give, returns whether they can all be applied.
+    fn update(&self, items: &[Item]) -> bool {
+        while let Some(row) = self.preview.first_child() {
+            self.preview.remove(&row);
+        }
+
+        let names = match preview(&self.rules(), items) {
+            Ok(names) => names,
+            Err(err) => {
+                self.error.set_label(&err);
+                self.error.set_visible(true);
+                return false;
+            }
+        };
+        self.error.set_visible(false);
+
+        for ((name, error), item) in names.iter().zip(items) {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            let old = gtk::Label::new(Some(&item.name));
+            old.add_css_class("dim-label");
+            row.append(&old);
+            row.append(&gtk::Label::new(Some("→")));
+
+            let new = gtk::Label::new(Some(name));
+            new.set_hexpand(true);
+            new.set_xalign(0.0);
+            row.append(&new);
+
+            if let Some(error) = error {
+                let label = gtk::Label::new(Some(error));
+                label.add_css_class("error");
+                row.append(&label);
+            }
+            self.preview.append(&row);
+        }
+
+        names.iter().all(|(_, error)| error.is_none())
+            && names.iter().zip(items).any(|((name, _), item)| *name != item.name)
+    }
+}
+
+fn labeled(label: &str, widgets: &[&gtk::Widget]) -> gtk::Box {
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    let label = gtk::Label::new(Some(label));
+    label.set_width_chars(10);
+    label.set_xalign(0.0);
+    row.append(&label);
+    for widget in widgets {
+        row.append(*widget);
+    }
+    row
+}
+
+/// Renames several files at once, previewing every new name before anything happens.
+pub async fn batch_rename_dialog(window: Window, files: Vec<gio::File>) {
+    let items: Rc<Vec<Item>> = Rc::new(
+        files
+            .into_iter()
+            .filter_map(|file| {
+                let info = file
+                    .query_info(
+                        "standard::display-name,time::modified",
+                        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                        None::<&gio::Cancellable>,
+                    )
+                    .ok()?;
+                Some(Item {
+                    name: info.display_name().to_string(),
+                    modified: info.modification_date_time(),
+                    file,
+                })
+            })
+            .collect(),
+    );
+
+    let positions = ["Before Name", "After Name"];
+    let form = Rc::new(Form {
+        find: gtk::Entry::builder().hexpand(true).build(),
+        replace: gtk::Entry::builder().hexpand(true).build(),
+        regex: gtk::CheckButton::with_label("Regular Expression"),
+        case: gtk::DropDown::from_strings(&["Keep Case", "lowercase", "UPPERCASE", "Title Case"]),
+        numbering: gtk::CheckButton::with_label("Add Number"),
+        start: gtk::SpinButton::with_range(0.0, 1_000_000.0, 1.0),
+        digits: gtk::SpinButton::with_range(1.0, 10.0, 1.0),
+        number_position: gtk::DropDown::from_strings(&positions),
+        date: gtk::CheckButton::with_label("Add Date"),
+        date_format: gtk::Entry::builder().text("%Y-%m-%d").hexpand(true).build(),
+        date_position: gtk::DropDown::from_strings(&positions),
+        preview: gtk::ListBox::new(),
+        error: gtk::Label::new(None),
+    });
+    form.start.set_value(1.0);
+    form.error.add_css_class("error");
+    form.error.set_xalign(0.0);
+    form.error.set_visible(false);
+    form.preview.set_selection_mode(gtk::SelectionMode::None);
+
+    let boxx = gtk::Box::builder()
+        .margin_top(8)
+        .margin_bottom(8)
+        .margin_start(8)
+        .margin_end(8)
+        .spacing(6)
+        .orientation(gtk::Orientation::Vertical)
+        .build();
+
+    boxx.append(&labeled("Find", &[form.find.upcast_ref(), form.regex.upcast_ref()]));
+    boxx.append(&labeled("Replace", &[form.replace.upcast_ref()]));
+    boxx.append(&labeled("Case", &[form.case.upcast_ref()]));
+    boxx.append(&labeled(
+        "Number",
+        &[
+            form.numbering.upcast_ref(),
+            form.start.upcast_ref(),
+            form.digits.upcast_ref(),
+            form.number_position.upcast_ref(),
+        ],
+    ));
+    boxx.append(&labeled(
+        "Date",
+        &[
+            form.date.upcast_ref(),
+            form.date_format.upcast_ref(),
+            form.date_position.upcast_ref(),
+        ],
+    ));
+    boxx.append(&form.error);
+    boxx.append(
+        &gtk::ScrolledWindow::builder()
+            .child(&form.preview)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_height(200)
+            .vexpand(true)
+            .build(),
+    );
+
+    let dialog = gtk::Dialog::builder()
+        .transient_for(&window)
+        .modal(true)
+        .use_header_bar(1)
+        .title(&format!("Rename {} Items", items.len()))
+        .default_width(560)
+        .child(&boxx)
+        .build();
+
+    dialog.add_buttons(&[
+        ("Cancel", gtk::ResponseType::Cancel),
+        ("Rename", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let update = Rc::new(glib::clone!(@weak dialog, @strong form, @strong items => move || {
+        let valid = form.update(&items);
+        dialog.set_response_sensitive(gtk::ResponseType::Accept, valid);
+    }));
+
+    for entry in [&form.find, &form.replace, &form.date_format] {
+        entry.connect_changed(glib::clone!(@strong update => move |_| update()));
+    }
+    for check in [&form.regex, &form.numbering, &form.date] {
+        check.connect_toggled(glib::clone!(@strong update => move |_| update()));
+    }
+    for spin in [&form.start, &form.digits] {
+        spin.connect_value_changed(glib::clone!(@strong update => move |_| update()));
+    }
+    for dropdown in [&form.case, &form.number_position, &form.date_position] {
+        dropdown.connect_selected_notify(glib::clone!(@strong update => move |_| update()));
+    }
+    update();
+
+    let answer = dialog.run_future().await;
+    dialog.close();
+    if answer != gtk::ResponseType::Accept {
+        return;
+    }
+
+    if let Ok(names) = preview(&form.rules(), &items) {
+        let pairs: Vec<(gio::File, gio::File)> = names
+            .into_iter()
+            .zip(items.iter())
+            .filter_map(|((name, _), item)| {
+                let target = item.file.parent()?.child(name);
+                Some((item.file.clone(), target))
+            })
+            .collect();
+        window.property::<Application>("application").rename(&pairs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::scratch_dir;
+
+    fn rename(rules: &Rules, names: &[&str]) -> Vec<String> {
+        let pattern = rules.pattern().expect("Error compiling test pattern");
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| rules.apply(pattern.as_ref(), name, index, None))
+            .collect()
+    }
+
+    fn items(dir: &gio::File, names: &[&str]) -> Vec<Item> {
+        names
+            .iter()
+            .map(|name| Item {
+                file: dir.child(name),
+                name: name.to_string(),
+                modified: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counters_count_from_start_and_keep_the_extension() {
+        let rules = Rules {
+            numbering: Some((9, 3, Position::After)),
+            ..Default::default()
+        };
+        assert_eq!(
+            rename(&rules, &["photo.jpg", "photo.png", ".hidden"]),
+            ["photo 009.jpg", "photo 010.png", ".hidden 011"]
+        );
+
+        let rules = Rules {
+            numbering: Some((1, 0, Position::Before)),
+            case: Case::Title,
+            ..Default::default()
+        };
+        assert_eq!(rename(&rules, &["holiday in rome.txt"]), ["1 Holiday In Rome.txt"]);
+    }
+
+    #[test]
+    fn regex_groups_expand_only_in_regex_mode() {
+        let mut rules = Rules {
+            find: r"(\w+)-(\d+)".to_owned(),
+            replace: "$2-$1".to_owned(),
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(rename(&rules, &["holiday-2022.png"]), ["2022-holiday.png"]);
+
+        rules.find = "holiday".to_owned();
+        rules.regex = false;
+        assert_eq!(rename(&rules, &["holiday-2022.png"]), ["$2-$1-2022.png"]);
+
+        rules.find = "(".to_owned();
+        rules.regex = true;
+        assert!(preview(&rules, &[]).is_err());
+    }
+
+    #[test]
+    fn duplicate_results_are_reported() {
+        let dir = scratch_dir("batch-rename-duplicates");
+        let rules = Rules {
+            find: r"\d".to_owned(),
+            regex: true,
+            ..Default::default()
+        };
+        let result = preview(&rules, &items(&dir, &["a1.txt", "a2.txt", "b.txt"]))
+            .expect("Error previewing test rename");
+
+        let duplicate = Some("Same name as another renamed item");
+        assert_eq!(result[0], ("a.txt".to_owned(), duplicate));
+        assert_eq!(result[1], ("a.txt".to_owned(), duplicate));
+        assert_eq!(result[2], ("b.txt".to_owned(), None));
+    }
+
+    #[test]
+    fn invalid_and_taken_names_are_reported() {
+        let dir = scratch_dir("batch-rename-invalid");
+        dir.child("taken.txt")
+            .create(gio::FileCreateFlags::NONE, None::<&gio::Cancellable>)
+            .expect("Error creating test file");
+
+        let rules = Rules {
+            find: "draft".to_owned(),
+            replace: "a/b".to_owned(),
+            ..Default::default()
+        };
+        let result = preview(&rules, &items(&dir, &["draft", "other"]))
+            .expect("Error previewing test rename");
+        assert_eq!(result[0].1, Some("Names can't contain \"/\""));
+        assert_eq!(result[1].1, None);
+
+        let rules = Rules {
+            find: "draft".to_owned(),
+            replace: "taken".to_owned(),
+            ..Default::default()
+        };
+        let result = preview(&rules, &items(&dir, &["draft.txt", "..draft"]))
+            .expect("Error previewing test rename");
+        assert_eq!(result[0].1, Some("An item with this name already exists"));
+        assert_eq!(result[1], ("..taken".to_owned(), None));
+
+        // An item keeping its own name doesn't collide with itself.
+        let result = preview(&rules, &items(&dir, &["taken.txt"]))
+            .expect("Error previewing test rename");
+        assert_eq!(result[0], ("taken.txt".to_owned(), None));
+    }
+}
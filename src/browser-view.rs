@@ -1,41 +1,383 @@
-use crate::utilities::Utilities;
+use crate::application::Application;
+use crate::query::Query;
+use crate::search;
+use crate::trash;
+use crate::utilities::{self, file_of};
 use glib::subclass::prelude::*;
 
-use gtk::{self, gio, glib::{self, clone}, prelude::*};
+use gtk::{self, gdk, gio, glib::{self, clone}, prelude::*};
 
 glib::wrapper! {
     pub struct BrowserView(ObjectSubclass<imp::BrowserView>) @extends gtk::Widget, @implements gtk::Buildable;
 }
 
+thread_local! {
+    /// Every list item the factories have set up, the views look their rows up in here.
+    static LIST_ITEMS: std::cell::RefCell<Vec<glib::WeakRef<gtk::ListItem>>> =
+        Default::default();
+}
+
+/// Remembers `item`, `true` the first time it is seen and its child needs its controllers.
+fn register_list_item(item: &gtk::ListItem) -> bool {
+    LIST_ITEMS.with(|items| {
+        let mut items = items.borrow_mut();
+        items.retain(|known| known.upgrade().is_some());
+        if items.iter().any(|known| known.upgrade().as_ref() == Some(item)) {
+            return false;
+        }
+        items.push(item.downgrade());
+        true
+    })
+}
+
+/// The list items currently bound to an item, whichever view they are in.
+fn bound_list_items() -> Vec<gtk::ListItem> {
+    LIST_ITEMS.with(|items| {
+        items
+            .borrow()
+            .iter()
+            .filter_map(|item| item.upgrade())
+            .filter(|item| item.item().is_some())
+            .collect()
+    })
+}
+
+/// Shows whether the item in `widget` is cut, see `Application::is_cut`.
+fn set_cut(widget: &gtk::Widget, cut: bool) {
+    if cut {
+        widget.add_css_class("cut");
+    } else {
+        widget.remove_css_class("cut");
+    }
+}
+
 impl BrowserView {
     pub fn for_path<P: AsRef<std::path::Path>>(path: P) -> Self {
         glib::Object::new(&[("dir", &gio::File::for_path(path))]).expect("Failed to create Window")
     }
 
-    pub fn search(&self, _query: String) {
-        // self.imp().sfilter.set_filter(Some(&gtk::CustomFilter::new(clone!(@strong self as s => move |obj| {
-        //     let info = obj
-        //         .downcast_ref::<gio::FileInfo>()
-        //         .expect("The object needs to be of type `IntegerObject`.");
-        //     let str = info.name().display().to_string();
+    /// Looks for `query` in the names, or the text, of everything below the folder the
+    /// view shows, the results take the place of the folder's content until `detach_search_view`.
+    ///
+    /// Filters alone narrow down the folder's content instead.
+    pub fn search(&self, query: Query, mode: search::Mode) {
+        let imp = self.imp();
+        self.set_query_filters(query.filters.gtk_filters());
+        self.stop_search();
+        if query.text.is_empty() {
+            return;
+        }
 
-        //     str.contains(&query)
-        // }))));
+        let dir = match self.property::<Option<gio::File>>("dir") {
+            Some(dir) => dir,
+            None => return,
+        };
+        let cancellable = gio::Cancellable::new();
+        imp.search.replace(Some(cancellable.clone()));
+        imp.search_status.set_label("Searching…");
+        imp.viewport.set_visible_child_name("search-page");
+
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let show_hidden = self.active();
+        let worker = cancellable.clone();
+        std::thread::spawn(move || match mode {
+            search::Mode::Names => {
+                search::search_names(&dir, &query, show_hidden, &worker, &sender)
+            }
+            search::Mode::Contents => {
+                search::search_contents(&dir, &query, show_hidden, &worker, &sender)
+            }
+        });
+
+        receiver.attach(
+            None,
+            clone!(@weak self as view => @default-return glib::Continue(false), move |message| {
+                // A newer search or leaving the search took over.
+                if cancellable.is_cancelled() {
+                    return glib::Continue(false);
+                }
+                let imp = view.imp();
+                match message {
+                    search::Message::Hits(hits) => {
+                        let infos: Vec<glib::Object> =
+                            hits.iter().map(|hit| hit.file_info().upcast()).collect();
+                        imp.sstore.splice(imp.sstore.n_items(), 0, &infos);
+                        glib::Continue(true)
+                    }
+                    search::Message::Progress(progress) => {
+                        imp.search_status.set_label(&format!(
+                            "Searching… {} items looked at",
+                            progress.items
+                        ));
+                        glib::Continue(true)
+                    }
+                    search::Message::Finished => {
+                        imp.search_status.set_label(&match imp.sfilter.n_items() {
+                            0 => "No results found".to_owned(),
+                            1 => "1 result".to_owned(),
+                            n => format!("{} results", n),
+                        });
+                        glib::Continue(false)
+                    }
+                    search::Message::Failed(error) => {
+                        imp.search_status.set_label(&format!("Search failed: {}", error));
+                        glib::Continue(false)
+                    }
+                }
+            }),
+        );
     }
 
     pub fn attach_search_view(&self, _entry: &gtk::SearchEntry) {
         self.imp().sort_model.set_model(Some(&self.imp().sstore));
-        //self.property::<gio::File>("dir").iter(&self.imp().sstore);
     }
 
+    /// Stops the search, if any, and shows the whole folder again.
     pub fn detach_search_view(&self) {
-        self.imp().sstore.remove_all();
-        self.imp()
-            .sort_model
-            .set_model(Some(&self.imp().list.get()));
+        self.set_query_filters(Vec::new());
+        self.stop_search();
+    }
+
+    /// Puts `filters` in the view's filter chain in place of those of the last query.
+    fn set_query_filters(&self, filters: Vec<gtk::Filter>) {
+        let imp = self.imp();
+        for filter in imp.query_filters.take() {
+            let position = (0..imp.filters.n_items())
+                .find(|&pos| imp.filters.item(pos).map_or(false, |item| &item == filter.upcast_ref::<glib::Object>()));
+            if let Some(position) = position {
+                imp.filters.remove(position);
+            }
+        }
+        for filter in &filters {
+            imp.filters.append(filter);
+        }
+        imp.query_filters.replace(filters);
     }
 
+    fn stop_search(&self) {
+        let imp = self.imp();
+        if let Some(cancellable) = imp.search.take() {
+            cancellable.cancel();
+        }
+        imp.sstore.remove_all();
+        imp.sort_model.set_model(Some(&imp.list.get()));
+        imp.viewport.set_visible_child_name(
+            if !imp.list.is_loading() && imp.list.n_items() == 0 {
+                "is-empty"
+            } else {
+                "not-empty"
+            },
+        );
+    }
+
+    /// Shows the folder `file` is in, with `file` selected.
+    pub fn open_containing_folder(&self, file: &gio::File) {
+        if let Some(dir) = file.parent() {
+            self.set_property("dir", dir);
+            self.reveal(file);
+        }
+    }
+
+    /// Opens a folder in the view and anything else in its default application.
+    fn open(&self, info: &gio::FileInfo) {
+        let file = match file_of(info) {
+            Some(file) => file,
+            None => return,
+        };
+
+        if info.file_type() == gio::FileType::Directory {
+            self.set_property("dir", file);
+        } else if let Some(app) = info
+            .content_type()
+            .and_then(|mime_type| gio::AppInfo::default_for_type(mime_type.as_str(), true))
+        {
+            let launched = app.launch(&[file], None::<&gdk::AppLaunchContext>);
+            let app = gio::Application::default().and_then(|app| app.downcast::<Application>().ok());
+            if let (Err(err), Some(app)) = (launched, app) {
+                app.toast(err.message());
+            }
+        }
+    }
+
+    /// Files selected in the view, in view order.
+    pub fn selected_files(&self) -> Vec<gio::File> {
+        let selected = gtk::SelectionFilterModel::new(Some(&self.shown_model()));
+        (0..selected.n_items())
+            .filter_map(|pos| selected.item(pos))
+            .filter_map(|item| item.downcast::<gio::FileInfo>().ok())
+            .filter_map(|info| file_of(&info))
+            .collect()
+    }
+
+    /// Whether search results are shown instead of the folder.
+    pub fn is_searching(&self) -> bool {
+        self.imp().search.borrow().is_some()
+    }
+
+    /// The model of the items on screen, the search results while searching.
+    fn shown_model(&self) -> gtk::MultiSelection {
+        if self.is_searching() {
+            self.imp().search_selection.get()
+        } else {
+            self.imp().model.get()
+        }
+    }
+
+    /// Position of `file` in `model`.
+    fn position(model: &impl IsA<gio::ListModel>, file: &gio::File) -> Option<u32> {
+        (0..model.n_items()).find(|&pos| {
+            model
+                .item(pos)
+                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| file_of(&info))
+                .map_or(false, |listed| listed.equal(file))
+        })
+    }
+
+    /// The widget currently showing `file`, if it is scrolled into view.
+    fn item_widget(&self, file: &gio::File) -> Option<gtk::Widget> {
+        let view: gtk::Widget = match self.imp().stack.visible_child_name().as_deref() {
+            _ if self.is_searching() => self.imp().search_view.get().upcast(),
+            Some("list-view") => self.imp().list_view.get().upcast(),
+            _ => self.imp().view.get().upcast(),
+        };
+
+        let model = self.shown_model();
+        let info = model.item(Self::position(&model, file)?)?;
+        bound_list_items()
+            .into_iter()
+            .filter(|item| item.item().as_ref() == Some(&info))
+            .filter_map(|item| item.child())
+            .find(|child| child.is_ancestor(&view))
+    }
+
+    /// Dims the items that were cut and wait to be pasted, and only those.
+    ///
+    /// Only the rows on screen are restyled, the others are styled when they are bound.
+    fn update_cut_state(&self) {
+        let app = match gio::Application::default().and_then(|app| app.downcast::<Application>().ok()) {
+            Some(app) => app,
+            None => return,
+        };
+
+        for item in bound_list_items() {
+            let child = match item.child() {
+                Some(child) if child.is_ancestor(self) => child,
+                _ => continue,
+            };
+            let cut = item
+                .item()
+                .and_then(|info| info.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| file_of(&info))
+                .map_or(false, |file| app.is_cut(&file));
+            set_cut(&child, cut);
+        }
+    }
+
+    /// Selects `file` alone among the items on screen, `false` while it isn't listed yet.
+    fn select_file(&self, file: &gio::File) -> bool {
+        let model = self.shown_model();
+        Self::position(&model, file).map_or(false, |pos| model.select_item(pos, true))
+    }
+
+    /// Runs `f` once `file` is listed and selected, until it returns `true`.
+    ///
+    /// A file that was just created may take a few frames to show up, so this keeps
+    /// trying for a little while.
+    fn when_listed(&self, file: &gio::File, f: impl Fn(&Self) -> bool + 'static) {
+        let frames = std::cell::Cell::new(0);
+        self.add_tick_callback(clone!(@strong file => move |view, _clock| {
+            frames.set(frames.get() + 1);
+            if view.select_file(&file) && f(view) {
+                glib::Continue(false)
+            } else {
+                glib::Continue(frames.get() < 120)
+            }
+        }));
+    }
+
+    /// Selects `file` alone as soon as it is listed.
+    pub fn reveal(&self, file: &gio::File) {
+        self.when_listed(file, |_| true);
+    }
+
+    /// Shows a rename field over `file`, the name is selected up to its extension.
+    pub fn start_rename(&self, file: &gio::File) {
+        self.when_listed(file, clone!(@strong file => move |view| {
+            match view.item_widget(&file) {
+                Some(widget) => {
+                    view.rename_popover(&widget, &file);
+                    true
+                }
+                None => false,
+            }
+        }));
+    }
+
+    fn rename_popover(&self, widget: &gtk::Widget, file: &gio::File) {
+        let dir = match file.parent() {
+            Some(dir) => dir,
+            None => return,
+        };
+        let name = file
+            .query_info(
+                "standard::display-name,standard::type",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                None::<&gio::Cancellable>,
+            )
+            .ok();
+        let is_dir = name.as_ref().map_or(false, |info| info.file_type() == gio::FileType::Directory);
+        let name = name.map(|info| info.display_name().to_string()).unwrap_or_default();
+
+        let entry = gtk::Entry::builder().text(&name).activates_default(true).build();
+        let error = gtk::Label::new(None);
+        error.set_xalign(0.0);
+        error.add_css_class("error");
+        error.set_visible(false);
+
+        let button = gtk::Button::with_label("Rename");
+        button.add_css_class("suggested-action");
+
+        let boxx = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row.append(&entry);
+        row.append(&button);
+        boxx.append(&gtk::Label::new(Some(if is_dir { "Folder name" } else { "File name" })));
+        boxx.append(&row);
+        boxx.append(&error);
+
+        let popover = gtk::Popover::new();
+        popover.set_child(Some(&boxx));
+        popover.set_parent(widget);
+        popover.set_default_widget(Some(&button));
+        popover.connect_closed(|popover| popover.unparent());
+
+        entry.connect_changed(clone!(@strong dir, @strong file, @weak error, @weak button => move |entry| {
+            let reason = utilities::new_name_error(&dir, &entry.text(), Some(&file));
+            error.set_label(reason.unwrap_or_default());
+            error.set_visible(reason.is_some());
+            button.set_sensitive(reason.is_none());
+        }));
+
+        button.connect_clicked(clone!(@strong file, @weak entry, @weak popover => move |_| {
+            let target = dir.child(entry.text().as_str());
+            if let Some(app) = gio::Application::default().and_then(|app| app.downcast::<Application>().ok()) {
+                app.rename(&[(file.clone(), target)]);
+            }
+            popover.popdown();
+        }));
+
+        popover.popup();
+        entry.grab_focus();
+        let stem = if is_dir { name.as_str() } else { utilities::split_extension(&name).0 };
+        entry.select_region(0, stem.chars().count() as i32);
+    }
 
+    /// Whether the view shows the trash rather than a regular folder.
+    pub fn is_trash(&self) -> bool {
+        self.property::<Option<gio::File>>("dir")
+            .map_or(false, |dir| trash::is_trash(&dir))
+    }
 
     // Navigation Methods
     pub fn go_backward(&self) {
@@ -72,6 +414,7 @@ impl BrowserView {
 
 mod imp {
     use glib::clone;
+    use crate::utilities::file_of;
     use gtk::glib::subclass::Signal;
     use once_cell::sync::Lazy;
     use std::cell::RefCell;
@@ -81,7 +424,7 @@ mod imp {
 
     use gtk::prelude::*;
     use gtk::subclass::prelude::*;
-    use gtk::{self, gdk, gio};
+    use gtk::{self, gio};
     use gtk::{glib, CompositeTemplate};
 
     #[derive(Debug, CompositeTemplate)]
@@ -89,6 +432,8 @@ mod imp {
     pub struct BrowserView {
         #[template_child]
         pub view: TemplateChild<gtk::GridView>,
+        #[template_child(id = "list-view")]
+        pub list_view: TemplateChild<gtk::ListView>,
 
         #[template_child]
         pub stack: TemplateChild<gtk::Stack>,
@@ -110,6 +455,16 @@ mod imp {
 
         pub sstore: gio::ListStore,
         pub sfilter: gtk::FilterListModel,
+        #[template_child(id = "search-view")]
+        pub search_view: TemplateChild<gtk::ListView>,
+        #[template_child(id = "search-selection")]
+        pub search_selection: TemplateChild<gtk::MultiSelection>,
+        #[template_child(id = "search-status")]
+        pub search_status: TemplateChild<gtk::Label>,
+        /// Stops the running search, set for as long as search results are shown.
+        pub search: RefCell<Option<gio::Cancellable>>,
+        /// Filters of the current query, in `filters` along with the view's own.
+        pub query_filters: RefCell<Vec<gtk::Filter>>,
         // pub filterMap: HashMap<String, gtk::CustomFilter>,
 
         pub history: Rc<RefCell<(gio::ListStore, u32)>>,
@@ -126,6 +481,9 @@ mod imp {
     impl BrowserView {
         #[template_callback(function = false)]
         fn filebrowser_loading_notify(&self) {
+            if self.search.borrow().is_some() {
+                return;
+            }
             self.viewport.set_visible_child_name(
                 if !self.list.is_loading() && self.list.n_items() == 0 {
                     "is-empty"
@@ -137,25 +495,12 @@ mod imp {
 
         #[template_callback]
         fn filebrowser_activate(view: &super::BrowserView, pos: u32, grid: &gtk::GridView) {
-            let single = grid
+            if let Some(info) = grid
                 .model()
                 .and_then(|g| g.item(pos))
-                .and_then(|o| o.downcast::<gio::FileInfo>().ok());
-
-            if let (Some(file_type), Some(mime_type), Some(file)) = (
-                single.as_ref().map(|s| s.file_type()),
-                single.as_ref().and_then(|s| s.content_type()),
-                single
-                    .and_then(|o| o.attribute_object("standard::file"))
-                    .and_then(|f| f.downcast::<gio::File>().ok()),
-            ) {
-                if file_type == gio::FileType::Directory {
-                    view.set_property("dir", file);
-                } else if let Some(info) = gio::AppInfo::default_for_type(mime_type.as_str(), true)
-                {
-                    info.launch(&[file], None::<&gdk::AppLaunchContext>)
-                        .expect("Error launching app");
-                }
+                .and_then(|o| o.downcast::<gio::FileInfo>().ok())
+            {
+                view.open(&info);
             }
         }
 
@@ -166,11 +511,72 @@ mod imp {
                 .and_then(|o| o.attribute_string("standard::display-name"))
         }
 
+        #[template_callback(function = false)]
+        fn filebrowser_get_trash_info(item: &gtk::ListItem) -> Option<String> {
+            item.item()
+                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| crate::trash::describe(&info))
+        }
+
+        #[template_callback(function = false)]
+        fn search_get_location(item: &gtk::ListItem) -> Option<String> {
+            item.item()
+                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| file_of(&info))
+                .and_then(|file| file.parent())
+                .map(|dir| dir.parse_name().to_string())
+        }
+
+        #[template_callback(function = false)]
+        fn search_get_matches(item: &gtk::ListItem) -> Option<glib::GString> {
+            item.item()
+                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| info.attribute_string(crate::search::MATCHES_ATTRIBUTE))
+        }
+
+        #[template_callback(function = false)]
+        fn search_has_matches(item: &gtk::ListItem) -> bool {
+            Self::search_get_matches(item).is_some()
+        }
+
+        #[template_callback(function = false)]
+        fn search_get_uri(item: &gtk::ListItem) -> Option<glib::Variant> {
+            item.item()
+                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                .and_then(|info| file_of(&info))
+                .map(|file| file.uri().to_variant())
+        }
+
         #[template_callback(function = false)]
         fn filebrowser_get_icon(item: &gtk::ListItem) -> Option<gio::Icon> {
+            let info = item.item().and_then(|item| item.downcast::<gio::FileInfo>().ok());
+
+            if let Some(c) = item.child() {
+                // The child stays with its list item while that is bound to one item after
+                // another, so its controllers are added once and look the item up when they run.
+                if super::register_list_item(item) {
+                    Self::add_controllers(item, &c);
+                }
+
+                let app = gio::Application::default()
+                    .and_then(|app| app.downcast::<crate::application::Application>().ok());
+                if let (Some(info), Some(app)) = (&info, app) {
+                    let cut = file_of(info).map_or(false, |file| app.is_cut(&file));
+                    super::set_cut(&c, cut);
+                }
+            }
+
+            info.and_then(|o| o.attribute_object("standard::icon"))
+                .and_then(|icon| icon.downcast::<gio::Icon>().ok())
+        }
+    }
+
+    impl BrowserView {
+        /// Right-click selection, dragging and, for folders, drops for the child of `item`.
+        fn add_controllers(item: &gtk::ListItem, c: &gtk::Widget) {
             let gesture = gtk::GestureClick::new();
             gesture.set_button(0);
-            gesture.connect_released(clone!(@strong item as item => move |gesture, _n_press, _x, _y| {
+            gesture.connect_released(clone!(@weak item => move |gesture, _n_press, _x, _y| {
                 if !item.is_selected() && gesture.current_button() == 3 {
                     item.child().map(|c| c.activate_action("listitem.select", Some(&(false, false).to_variant())));
                 }
@@ -182,42 +588,56 @@ mod imp {
             //         .and_then(|item| item.downcast::<gio::File>().ok());
             //     dbg!(item);
             // }
+            c.add_controller(&gesture);
 
             let drag = gtk::DragSource::new();
-            drag.set_actions(gdk::DragAction::COPY);
-
-            drag.connect_prepare(clone!(@strong item => move |_src, _x, _y| {
-                let item = item.item().and_then(|item| item.downcast::<gio::FileInfo>().ok())
-                    .and_then(|info| info.attribute_object("standard::file"))
-                    .and_then(|item| item.downcast::<gio::File>().ok());
-                // let canvas = src.widget();
-                // let picked = canvas.pick(x, y, gtk::PickFlags::DEFAULT).expect("Error picking widget");
-                // let a = picked.ancestor(gtk::ListItem::static_type());
-                Some(gdk::ContentProvider::for_value(&item.to_value()))
+            drag.set_actions(crate::dnd::ACTIONS);
+
+            drag.connect_prepare(clone!(@weak item => @default-return None, move |src, _x, _y| {
+                let file = item.item().and_then(|item| item.downcast::<gio::FileInfo>().ok())
+                    .and_then(|info| file_of(&info))?;
+
+                // Dragging a selected item takes the whole selection along.
+                let view = src.widget()
+                    .ancestor(super::BrowserView::static_type())
+                    .and_then(|view| view.downcast::<super::BrowserView>().ok());
+                let files = match view {
+                    Some(view) if item.is_selected() => view.selected_files(),
+                    _ => vec![file],
+                };
+                Some(crate::dnd::content(&files))
             }));
-            drag.connect_begin(clone!(@strong item => move |src, _event| {
+            drag.connect_begin(clone!(@weak item => move |src, _event| {
                 if let Some(child) = item.child() {
                     let paintable = gtk::WidgetPaintable::new(Some(&child));
                     src.set_icon(Some(&paintable), child.allocated_width(), child.allocated_height());
                     child.set_opacity(0.8);
                 }
             }));
-            drag.connect_end(clone!(@strong item => move |_src, _event| {
+            drag.connect_end(clone!(@weak item => move |_src, _event| {
                 let child = item.child();
                 child.map(|c| c.set_opacity(1.0));
             }));
-
-            if let Some(c) = item.child() {
-                c.add_controller(&drag)
-            }
-            if let Some(c) = item.child() {
-                c.add_controller(&gesture)
-            }
-
-            item.item()
-                .and_then(|item| item.downcast::<gio::FileInfo>().ok())
-                .and_then(|o| o.attribute_object("standard::icon"))
-                .and_then(|icon| icon.downcast::<gio::Icon>().ok())
+            c.add_controller(&drag);
+
+            // Folders take drops and open when hovered, the target looks the folder up
+            // at drop time.
+            let folder = clone!(@weak item => @default-return None, move || {
+                let info = item.item().and_then(|item| item.downcast::<gio::FileInfo>().ok())?;
+                if info.file_type() != gio::FileType::Directory {
+                    return None;
+                }
+                file_of(&info)
+            });
+            let open = clone!(@weak c, @strong folder => move || {
+                let view = c.ancestor(super::BrowserView::static_type())
+                    .and_then(|view| view.downcast::<super::BrowserView>().ok());
+                if let (Some(view), Some(dir)) = (view, folder()) {
+                    view.set_property("dir", dir);
+                }
+            });
+            let target = crate::dnd::drop_target(folder, Some(Box::new(open)));
+            c.add_controller(&target);
         }
     }
 
@@ -232,6 +652,12 @@ mod imp {
             Self::bind_template(klass);
             Self::bind_template_callbacks(klass);
             klass.set_layout_manager_type::<gtk::BoxLayout>();
+
+            klass.install_action("view.open-containing-folder", Some("s"), |view, _, target| {
+                if let Some(uri) = target.and_then(|target| target.get::<String>()) {
+                    view.open_containing_folder(&gio::File::for_uri(&uri));
+                }
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -253,6 +679,7 @@ mod imp {
 
             Self {
                 view: Default::default(),
+                list_view: Default::default(),
                 stack: Default::default(),
                 viewport: Default::default(),
                 list: Default::default(),
@@ -264,6 +691,11 @@ mod imp {
 
                 sstore,
                 sfilter,
+                search_view: Default::default(),
+                search_selection: Default::default(),
+                search_status: Default::default(),
+                search: Default::default(),
+                query_filters: Default::default(),
 
                 history: Rc::new(RefCell::new((
                     gio::ListStore::new(gio::File::static_type()),
@@ -281,6 +713,32 @@ mod imp {
     impl ObjectImpl for BrowserView {
         fn constructed(&self, obj: &Self::Type) {
             self.filters.append(&self.hidden_filter);
+
+            self.search_selection.set_model(Some(&self.sfilter));
+            self.search_view.connect_activate(clone!(@weak obj => move |list, pos| {
+                if let Some(info) = list
+                    .model()
+                    .and_then(|model| model.item(pos))
+                    .and_then(|o| o.downcast::<gio::FileInfo>().ok())
+                {
+                    obj.open(&info);
+                }
+            }));
+
+            // Dropping on the background puts things in the folder the view shows.
+            obj.add_controller(&crate::dnd::drop_target(
+                clone!(@weak obj => @default-return None, move || obj.property::<Option<gio::File>>("dir")),
+                None,
+            ));
+
+            if let Some(app) = gio::Application::default()
+                .and_then(|app| app.downcast::<crate::application::Application>().ok())
+            {
+                app.imp().0 .0.connect_items_changed(clone!(@weak obj => move |_, _, _, _| {
+                    obj.update_cut_state();
+                }));
+            }
+
             self.parent_constructed(obj);
         }
 
@@ -330,7 +788,7 @@ mod imp {
 
         fn set_property(
             &self,
-            _obj: &Self::Type,
+            obj: &Self::Type,
             _id: usize,
             value: &glib::Value,
             pspec: &glib::ParamSpec,
@@ -353,6 +811,10 @@ mod imp {
                 },
 
                 "dir" => if let Ok(value) = value.get::<gio::File>() {
+                    // Results below the folder being left make no sense anymore.
+                    if self.search.borrow().is_some() {
+                        obj.detach_search_view();
+                    }
                     self.list.set_file(Some(&value));
 
                     let (store, pos) = &mut *self.history.borrow_mut();
@@ -0,0 +1,108 @@
+use gtk::prelude::*;
+use gtk::{self, gdk, gio, glib};
+
+use crate::application::ProcessType;
+
+/// What GNOME file managers put on the clipboard, `copy` or `cut` followed by one URI per line.
+const GNOME_COPIED_FILES: &str = "x-special/gnome-copied-files";
+const URI_LIST: &str = "text/uri-list";
+
+pub fn clipboard() -> Option<gdk::Clipboard> {
+    gdk::Display::default().map(|display| display.clipboard())
+}
+
+/// Whether the clipboard holds files, from this app or any other.
+pub fn has_files(clipboard: &gdk::Clipboard) -> bool {
+    let formats = clipboard.formats();
+    formats.contain_mime_type(GNOME_COPIED_FILES) || formats.contain_mime_type(URI_LIST)
+}
+
+/// `files` as a `text/uri-list`, understood by about every application taking files.
+pub fn uri_list(files: &[gio::File]) -> String {
+    files
+        .iter()
+        .map(|file| format!("{}\r\n", file.uri()))
+        .collect()
+}
+
+/// `files` as plain text, one path per line, for terminals and text editors.
+pub fn paths(files: &[gio::File]) -> String {
+    files
+        .iter()
+        .map(|file| file.parse_name().to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Puts `files` on the clipboard, to be copied or moved by whoever pastes them.
+pub fn set(clipboard: &gdk::Clipboard, action: ProcessType, files: &[gio::File]) {
+    let uris: Vec<String> = files.iter().map(|file| file.uri().to_string()).collect();
+    let verb = match action {
+        ProcessType::Copy => "copy",
+        ProcessType::Move => "cut",
+    };
+    let gnome = format!("{}\n{}", verb, uris.join("\n"));
+
+    let provider = gdk::ContentProvider::new_union(&[
+        gdk::ContentProvider::for_bytes(GNOME_COPIED_FILES, &glib::Bytes::from_owned(gnome)),
+        gdk::ContentProvider::for_bytes(URI_LIST, &glib::Bytes::from_owned(uri_list(files))),
+        gdk::ContentProvider::for_bytes(
+            "text/plain;charset=utf-8",
+            &glib::Bytes::from_owned(paths(files)),
+        ),
+    ]);
+    let _ = clipboard.set_content(Some(&provider));
+}
+
+/// Files in a `text/uri-list`, comments and blank lines left out.
+pub fn parse_uri_list(text: &str) -> Vec<gio::File> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(gio::File::for_uri)
+        .collect()
+}
+
+async fn read_all(clipboard: &gdk::Clipboard, mime_type: &str) -> Option<String> {
+    let (stream, _) = clipboard
+        .read_future(&[mime_type], glib::PRIORITY_DEFAULT)
+        .await
+        .ok()?;
+
+    let mut content = Vec::new();
+    loop {
+        let bytes = stream
+            .read_bytes_future(64 * 1024, glib::PRIORITY_DEFAULT)
+            .await
+            .ok()?;
+        if bytes.is_empty() {
+            break;
+        }
+        content.extend_from_slice(&bytes);
+    }
+    String::from_utf8(content).ok()
+}
+
+/// The files on the clipboard and whether they were copied or cut.
+///
+/// Plain URI lists, from browsers and the like, are always copied.
+pub async fn read(clipboard: &gdk::Clipboard) -> Option<(ProcessType, Vec<gio::File>)> {
+    let formats = clipboard.formats();
+
+    if formats.contain_mime_type(GNOME_COPIED_FILES) {
+        let text = read_all(clipboard, GNOME_COPIED_FILES).await?;
+        let (verb, uris) = text.split_once('\n').unwrap_or((&text, ""));
+        let action = match verb.trim() {
+            "cut" => ProcessType::Move,
+            _ => ProcessType::Copy,
+        };
+        return Some((action, parse_uri_list(uris)));
+    }
+
+    if formats.contain_mime_type(URI_LIST) {
+        let text = read_all(clipboard, URI_LIST).await?;
+        return Some((ProcessType::Copy, parse_uri_list(&text)));
+    }
+
+    None
+}
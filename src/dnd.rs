@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk::prelude::*;
+use gtk::{self, gdk, gio, glib, glib::clone};
+
+use crate::application::{Application, ProcessType};
+use crate::clipboard;
+use crate::file_operation;
+
+pub const ACTIONS: gdk::DragAction = gdk::DragAction::from_bits_truncate(
+    gdk::DragAction::COPY.bits() | gdk::DragAction::MOVE.bits() | gdk::DragAction::LINK.bits(),
+);
+
+/// How long a folder has to be hovered during a drag before it opens.
+const SPRING_DELAY: Duration = Duration::from_millis(800);
+
+/// Types a drop of files can come in.
+pub fn types() -> [glib::Type; 2] {
+    [gdk::FileList::static_type(), gio::File::static_type()]
+}
+
+/// What a drag of `files` carries, GTK turns the URI list back into a `gdk::FileList`
+/// for drops within the app, other apps take it as it is.
+pub fn content(files: &[gio::File]) -> gdk::ContentProvider {
+    gdk::ContentProvider::new_union(&[
+        gdk::ContentProvider::for_bytes(
+            "text/uri-list",
+            &glib::Bytes::from_owned(clipboard::uri_list(files)),
+        ),
+        gdk::ContentProvider::for_bytes(
+            "text/plain;charset=utf-8",
+            &glib::Bytes::from_owned(clipboard::paths(files)),
+        ),
+    ])
+}
+
+/// The files carried by a drop, whichever of `types` it came as.
+pub fn files(value: &glib::Value) -> Vec<gio::File> {
+    if let Ok(list) = value.get::<gdk::FileList>() {
+        list.files()
+    } else if let Ok(file) = value.get::<gio::File>() {
+        vec![file]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Whether `files` can be dropped in `dir`, a folder never goes inside itself.
+pub fn accepts(files: &[gio::File], dir: &gio::File) -> bool {
+    !files.is_empty()
+        && files
+            .iter()
+            .all(|file| !file.equal(dir) && !dir.has_prefix(file))
+}
+
+/// Moves within a filesystem and copies across filesystems, like everyone expects.
+pub fn default_action(same_device: bool) -> gdk::DragAction {
+    if same_device {
+        gdk::DragAction::MOVE
+    } else {
+        gdk::DragAction::COPY
+    }
+}
+
+/// Shift moves, Ctrl copies and both together make links, `None` leaves it to
+/// `default_action`.
+pub fn action(modifiers: gdk::ModifierType) -> Option<gdk::DragAction> {
+    let shift = modifiers.contains(gdk::ModifierType::SHIFT_MASK);
+    let control = modifiers.contains(gdk::ModifierType::CONTROL_MASK);
+    match (shift, control) {
+        (true, true) => Some(gdk::DragAction::LINK),
+        (true, false) => Some(gdk::DragAction::MOVE),
+        (false, true) => Some(gdk::DragAction::COPY),
+        (false, false) => None,
+    }
+}
+
+/// Finds out whether `files` and `dir` are on the same filesystem and calls `then` with
+/// the answer, the lookup runs on a thread of its own since it may block on a slow mount.
+fn same_device_async(files: &[gio::File], dir: &gio::File, then: impl FnOnce(bool) + 'static) {
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let (files, dir) = (files.to_vec(), dir.clone());
+    std::thread::spawn(move || {
+        let sources = file_operation::devices(&files);
+        let _ = sender.send(!sources.is_empty() && sources == file_operation::devices(&[dir]));
+    });
+
+    let mut then = Some(then);
+    receiver.attach(None, move |same_device| {
+        if let Some(then) = then.take() {
+            then(same_device);
+        }
+        glib::Continue(false)
+    });
+}
+
+/// The filesystem lookup for the files dragged over a target, done once per drag.
+struct Lookup {
+    files: Vec<gio::File>,
+    dir: gio::File,
+    /// `None` until the answer came.
+    same_device: Option<bool>,
+}
+
+impl Lookup {
+    fn is_for(&self, files: &[gio::File], dir: &gio::File) -> bool {
+        self.dir.equal(dir)
+            && self.files.len() == files.len()
+            && self.files.iter().zip(files).all(|(a, b)| a.equal(b))
+    }
+}
+
+/// Whether `files` and `dir` are on the same filesystem as far as `lookup` knows, `None`
+/// while that is looked up. Other files or another folder start a new lookup.
+fn same_device(
+    lookup: &Rc<RefCell<Option<Lookup>>>,
+    files: &[gio::File],
+    dir: &gio::File,
+) -> Option<bool> {
+    if let Some(known) = lookup.borrow().as_ref().filter(|known| known.is_for(files, dir)) {
+        return known.same_device;
+    }
+
+    lookup.replace(Some(Lookup {
+        files: files.to_vec(),
+        dir: dir.clone(),
+        same_device: None,
+    }));
+    let weak = Rc::downgrade(lookup);
+    let (for_files, for_dir) = (files.to_vec(), dir.clone());
+    same_device_async(files, dir, move |same_device| {
+        if let Some(lookup) = weak.upgrade() {
+            let mut lookup = lookup.borrow_mut();
+            let known = lookup.as_mut().filter(|known| known.is_for(&for_files, &for_dir));
+            if let Some(known) = known {
+                known.same_device = Some(same_device);
+            }
+        }
+    });
+    None
+}
+
+/// Modifiers held on the keyboard right now, for drops that don't come with an event state.
+pub fn current_modifiers() -> gdk::ModifierType {
+    gdk::Display::default()
+        .and_then(|display| display.default_seat())
+        .and_then(|seat| seat.keyboard())
+        .map_or(gdk::ModifierType::empty(), |keyboard| keyboard.modifier_state())
+}
+
+/// Runs a drop of `files` in `dir` through the usual job queue.
+///
+/// Without an `action` the drop moves or copies by `default_action`, once the filesystems
+/// were looked up.
+pub fn perform(files: Vec<gio::File>, dir: &gio::File, action: Option<gdk::DragAction>) -> bool {
+    let app = match gio::Application::default().and_then(|app| app.downcast::<Application>().ok()) {
+        Some(app) => app,
+        None => return false,
+    };
+
+    let action = match action {
+        Some(action) => action,
+        None => {
+            let (sources, dest) = (files.clone(), dir.clone());
+            same_device_async(&files, dir, move |same_device| {
+                perform(sources, &dest, Some(default_action(same_device)));
+            });
+            return true;
+        }
+    };
+
+    if action.contains(gdk::DragAction::LINK) {
+        app.make_links(&files, Some(dir), false);
+    } else if action.contains(gdk::DragAction::MOVE) {
+        app.do_sync(ProcessType::Move, files, dir);
+    } else if action.contains(gdk::DragAction::COPY) {
+        app.do_sync(ProcessType::Copy, files, dir);
+    } else {
+        return false;
+    }
+    true
+}
+
+/// A drop target for files, `dest` tells where they go at the time of the drop and
+/// `None` lets the drop through to whatever is below.
+///
+/// With `spring`, hovering the target for a moment calls it, folders use that to open.
+pub fn drop_target(
+    dest: impl Fn() -> Option<gio::File> + 'static,
+    spring: Option<Box<dyn Fn()>>,
+) -> gtk::DropTarget {
+    let target = gtk::DropTarget::new(glib::Type::INVALID, ACTIONS);
+    target.set_types(&types());
+    // The files are needed to pick an action before anything is dropped.
+    target.set_preload(true);
+
+    let dest = Rc::new(dest);
+    // Filesystems are looked up once for the files and folder of a drag, not for every
+    // motion. Until the answer comes, the drag offers to copy.
+    let lookup: Rc<RefCell<Option<Lookup>>> = Rc::default();
+    let preferred = Rc::new(clone!(@strong dest, @strong lookup => move |target: &gtk::DropTarget| {
+        let files = target.value().map(|value| files(&value)).unwrap_or_default();
+        match dest() {
+            Some(dir) if accepts(&files, &dir) => action(target.current_event_state())
+                .or_else(|| same_device(&lookup, &files, &dir).map(default_action))
+                .unwrap_or(gdk::DragAction::COPY),
+            _ => gdk::DragAction::empty(),
+        }
+    }));
+
+    let timeout: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+    let spring: Option<Rc<dyn Fn()>> = spring.map(Rc::from);
+    let stop_spring = clone!(@strong timeout, @strong lookup => move || {
+        if let Some(id) = timeout.take() {
+            id.remove();
+        }
+        lookup.replace(None);
+    });
+
+    target.connect_enter(clone!(@strong preferred, @strong timeout => move |target, _x, _y| {
+        if let Some(spring) = spring.clone() {
+            let fired = timeout.clone();
+            timeout.replace(Some(glib::timeout_add_local_once(SPRING_DELAY, move || {
+                fired.take();
+                spring();
+            })));
+        }
+        preferred(target)
+    }));
+    target.connect_motion(clone!(@strong preferred => move |target, _x, _y| preferred(target)));
+    target.connect_leave(clone!(@strong stop_spring => move |_| stop_spring()));
+
+    target.connect_drop(move |target, value, _x, _y| {
+        let files = files(value);
+        let result = match dest() {
+            Some(dir) if accepts(&files, &dir) => {
+                let known = lookup
+                    .borrow()
+                    .as_ref()
+                    .filter(|known| known.is_for(&files, &dir))
+                    .and_then(|known| known.same_device);
+                let action =
+                    action(target.current_event_state()).or_else(|| known.map(default_action));
+                perform(files, &dir, action)
+            }
+            _ => false,
+        };
+        stop_spring();
+        result
+    });
+
+    target
+}
@@ -0,0 +1,996 @@
+use std::cell::Cell;
+use std::sync::{mpsc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use gtk::{self, gio, glib};
+
+use gio::prelude::*;
+
+use crate::application::ProcessType;
+use crate::trash;
+use crate::utilities;
+use crate::walker::Walker;
+
+/// Filesystems on which two names differing only in case point to the same file.
+const CASE_INSENSITIVE_FILESYSTEMS: &[&str] = &[
+    "vfat", "msdos", "exfat", "ntfs", "ntfs3", "fuseblk", "hfs", "hfsplus", "apfs", "cifs",
+    "smb2", "smbfs",
+];
+
+/// Minimum time between two progress messages, so the main loop isn't flooded.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sent by a running job to its `ProgressInfoModel` on the main thread.
+#[derive(Debug)]
+pub enum Message {
+    Progress { done: i64, total: i64 },
+    CurrentFile(String),
+    Finished,
+    Failed,
+    Cancelled,
+    /// Items that now exist at their target, as `(source, target)`, sent before the job ends.
+    Completed(Vec<(gio::File, gio::File)>),
+    Conflict(Conflict),
+    Error(FileError),
+    Summary(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Replace,
+    Skip,
+    KeepBoth,
+    /// Only offered when a folder collides with a folder.
+    Merge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    Retry,
+    Skip,
+    /// Skips this and every later error of the job without asking again.
+    SkipAll,
+    Abort,
+}
+
+/// A file the job couldn't process, it waits on `reply` for what to do next.
+#[derive(Debug)]
+pub struct FileError {
+    pub file: gio::File,
+    pub error: glib::Error,
+    pub reply: mpsc::Sender<ErrorAction>,
+}
+
+impl FileError {
+    /// What went wrong, in words a user can act on.
+    pub fn description(&self) -> String {
+        let reason = match self.error.kind::<gio::IOErrorEnum>() {
+            Some(gio::IOErrorEnum::PermissionDenied) => "Permission denied".to_owned(),
+            Some(gio::IOErrorEnum::NoSpace) => "Not enough space on the destination".to_owned(),
+            Some(gio::IOErrorEnum::FilenameTooLong) => "The name is too long".to_owned(),
+            Some(gio::IOErrorEnum::InvalidFilename) => {
+                "The name isn't allowed on the destination".to_owned()
+            }
+            Some(gio::IOErrorEnum::ReadOnly) => "The destination is read-only".to_owned(),
+            Some(gio::IOErrorEnum::NotFound) => "The file no longer exists".to_owned(),
+            Some(gio::IOErrorEnum::Busy) => "The file is in use".to_owned(),
+            _ => self.error.message().to_owned(),
+        };
+
+        format!(
+            "{}: {}",
+            self.file
+                .basename()
+                .map(|name| name.display().to_string())
+                .unwrap_or_default(),
+            reason
+        )
+    }
+}
+
+/// Lets the main thread hold a running job between two chunks of I/O.
+#[derive(Debug, Default)]
+pub struct Pause {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl Pause {
+    pub fn set(&self, paused: bool) {
+        *self.paused.lock().expect("Error locking pause state") = paused;
+        self.resumed.notify_all();
+    }
+
+    /// Blocks while paused, a cancelled job is let through so it can wind down.
+    fn wait(&self, cancellable: &gio::Cancellable) {
+        let mut paused = self.paused.lock().expect("Error locking pause state");
+        while *paused && !cancellable.is_cancelled() {
+            paused = self
+                .resumed
+                .wait_timeout(paused, PROGRESS_INTERVAL)
+                .expect("Error locking pause state")
+                .0;
+        }
+    }
+}
+
+/// Where an item ends up once conflicts at its target are resolved.
+enum Destination {
+    Skip,
+    Create(gio::File, gio::FileCopyFlags),
+    /// Takes the place of an item that was set aside, see `Transfer::set_aside`.
+    Replace(gio::File, gio::FileCopyFlags),
+    Merge(gio::File),
+}
+
+/// An item that already exists at the destination, the job waits on `reply`
+/// for the user's choice and whether it applies to the rest of the job.
+#[derive(Debug)]
+pub struct Conflict {
+    pub src: gio::File,
+    pub existing: gio::File,
+    pub is_merge: bool,
+    pub reply: mpsc::Sender<(Resolution, bool)>,
+}
+
+/// Runs a copy or move of each source in `items` to the target paired with it.
+///
+/// This is blocking and is meant to be called from a worker thread, byte counts over
+/// the whole job are reported through `sender` and it ends with `Message::Finished`,
+/// `Message::Failed` or, once `cancellable` is triggered, `Message::Cancelled`.
+pub fn transfer(
+    action: ProcessType,
+    items: &[(gio::File, gio::File)],
+    cancellable: &gio::Cancellable,
+    pause: &Pause,
+    sender: &glib::Sender<Message>,
+) {
+    let sizes: Vec<i64> = items
+        .iter()
+        .map(|(src, _)| tree_size(src, cancellable))
+        .collect();
+
+    let mut transfer = Transfer::new(cancellable, pause, sender, sizes.iter().sum());
+
+    for ((src, target), size) in items.iter().zip(sizes) {
+        let done = transfer.done;
+        let result = cancellable
+            .set_error_if_cancelled()
+            .and_then(|_| match action {
+                ProcessType::Copy => transfer.copy(src, target),
+                ProcessType::Move => transfer.move_(src, target),
+            });
+
+        if let Err(err) = result {
+            transfer.remove_created();
+            transfer.restore_set_aside();
+            transfer.send(Message::Completed(std::mem::take(&mut transfer.completed)));
+            return transfer.fail(&err);
+        }
+
+        // Whatever this item left behind is complete and must survive a later cancel.
+        transfer.created.clear();
+        transfer.drop_set_aside();
+        transfer.done = done + size;
+        transfer.report(0);
+    }
+
+    transfer.send(Message::Completed(std::mem::take(&mut transfer.completed)));
+    transfer.finish();
+}
+
+/// Deletes each of `files` for good, folders along with everything in them.
+///
+/// Blocking like `transfer`, and reported the same way. Whatever was deleted before
+/// the job is cancelled stays deleted.
+pub fn delete(
+    files: &[gio::File],
+    cancellable: &gio::Cancellable,
+    pause: &Pause,
+    sender: &glib::Sender<Message>,
+) {
+    let total_size = files.iter().map(|file| tree_size(file, cancellable)).sum();
+    let mut transfer = Transfer::new(cancellable, pause, sender, total_size);
+
+    for file in files {
+        if let Err(err) = cancellable
+            .set_error_if_cancelled()
+            .and_then(|_| transfer.delete(file))
+        {
+            return transfer.fail(&err);
+        }
+    }
+
+    transfer.finish();
+}
+
+/// Filesystem ids of `files`, two jobs sharing one of them compete for the same disk.
+pub fn devices(files: &[gio::File]) -> Vec<String> {
+    let mut devices: Vec<String> = files
+        .iter()
+        .filter_map(|file| {
+            file.query_info(
+                "id::filesystem",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                None::<&gio::Cancellable>,
+            )
+            .ok()
+            .and_then(|info| info.attribute_string("id::filesystem"))
+            .map(|id| id.to_string())
+        })
+        .collect();
+    devices.sort();
+    devices.dedup();
+    devices
+}
+
+/// `name` in `dir`, or the first free variant of it when that's taken.
+pub fn available_child(dir: &gio::File, name: &str) -> gio::File {
+    let child = dir.child(name);
+    if child.query_exists(None::<&gio::Cancellable>) {
+        unique_child(dir, name)
+    } else {
+        child
+    }
+}
+
+/// Free name in `dir` for a duplicate of `name`, as in `name (copy).ext` or `name (copy 2).ext`.
+pub fn duplicate_child(dir: &gio::File, name: &str) -> gio::File {
+    let (stem, extension) = utilities::split_extension(name);
+
+    (1..)
+        .map(|n| match n {
+            1 => dir.child(format!("{} (copy){}", stem, extension)),
+            n => dir.child(format!("{} (copy {}){}", stem, n, extension)),
+        })
+        .find(|file| !file.query_exists(None::<&gio::Cancellable>))
+        .expect("Error finding a free name")
+}
+
+/// First child of `dir` named after `name` that doesn't exist yet, as in `name (2).ext`.
+pub fn unique_child(dir: &gio::File, name: &str) -> gio::File {
+    let (stem, extension) = utilities::split_extension(name);
+
+    (2..)
+        .map(|n| dir.child(format!("{} ({}){}", stem, n, extension)))
+        .find(|file| !file.query_exists(None::<&gio::Cancellable>))
+        .expect("Error finding a free name")
+}
+
+/// Size in bytes of `file`, including everything below it when it is a directory.
+fn tree_size(file: &gio::File, cancellable: &gio::Cancellable) -> i64 {
+    let info = match file.query_info(
+        "standard::type,standard::size",
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        Some(cancellable),
+    ) {
+        Ok(info) => info,
+        Err(_) => return 0,
+    };
+
+    if info.file_type() != gio::FileType::Directory {
+        return info.size();
+    }
+
+    Walker::new(file)
+        .walk(cancellable, |_, _| Ok(()))
+        .map_or(0, |progress| progress.size)
+}
+
+struct Transfer<'a> {
+    cancellable: &'a gio::Cancellable,
+    pause: &'a Pause,
+    sender: &'a glib::Sender<Message>,
+    total_size: i64,
+    done: i64,
+    last_report: Cell<Instant>,
+    /// Files and folders written for the item in flight, in creation order.
+    created: Vec<gio::File>,
+    /// Items the item in flight replaces, as `(original, parked)`.
+    set_aside: Vec<(gio::File, gio::File)>,
+    /// Items that fully made it to a target of their own, everything else can't be undone.
+    completed: Vec<(gio::File, gio::File)>,
+    apply_to_all: Option<Resolution>,
+    apply_to_all_folders: Option<Resolution>,
+    skip_all_errors: bool,
+    merged: usize,
+    skipped: usize,
+    replaced: usize,
+}
+
+impl<'a> Transfer<'a> {
+    fn new(
+        cancellable: &'a gio::Cancellable,
+        pause: &'a Pause,
+        sender: &'a glib::Sender<Message>,
+        total_size: i64,
+    ) -> Self {
+        Self {
+            cancellable,
+            pause,
+            sender,
+            total_size,
+            done: 0,
+            last_report: Cell::new(Instant::now()),
+            created: Vec::new(),
+            set_aside: Vec::new(),
+            completed: Vec::new(),
+            apply_to_all: None,
+            apply_to_all_folders: None,
+            skip_all_errors: false,
+            merged: 0,
+            skipped: 0,
+            replaced: 0,
+        }
+    }
+
+    /// Reports to the main thread, nobody may be listening anymore once the window is gone.
+    ///
+    /// Questions sent then are answered with a disconnect, and cancellation stops the job.
+    fn send(&self, message: Message) {
+        let _ = self.sender.send(message);
+    }
+
+    /// Reports `current` bytes of the file in flight on top of what is already done.
+    ///
+    /// This runs between two chunks of a copy, which makes it the place to hold a paused job.
+    fn report(&self, current: i64) {
+        self.pause.wait(self.cancellable);
+
+        if self.last_report.get().elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_report.set(Instant::now());
+        self.send(Message::Progress {
+            done: self.done + current,
+            total: self.total_size,
+        });
+    }
+
+    /// Removes what a cancelled item had written so far, children before their folders.
+    fn remove_created(&mut self) {
+        for file in self.created.drain(..).rev() {
+            let _ = file.delete(None::<&gio::Cancellable>);
+        }
+    }
+
+    /// Moves `existing` out of the way of what replaces it, it is only deleted once the
+    /// item in flight made it and comes back otherwise.
+    fn set_aside(&mut self, existing: &gio::File) -> Result<(), glib::Error> {
+        let dir = existing.parent().expect("Error getting parent directory");
+        let name = existing.basename().expect("Error getting file name");
+        let parked = unique_child(&dir, &format!(".{}.replaced", name.to_string_lossy()));
+        existing.move_(
+            &parked,
+            gio::FileCopyFlags::NOFOLLOW_SYMLINKS | gio::FileCopyFlags::NO_FALLBACK_FOR_MOVE,
+            Some(self.cancellable),
+            None,
+        )?;
+        self.set_aside.push((existing.clone(), parked));
+        Ok(())
+    }
+
+    /// Deletes what the item in flight replaced, now that it made it.
+    fn drop_set_aside(&mut self) {
+        for (_, parked) in self.set_aside.drain(..) {
+            let _ = delete_tree(&parked, &gio::Cancellable::new());
+        }
+    }
+
+    /// Puts back what a failed item was going to replace, unless its replacement was kept.
+    fn restore_set_aside(&mut self) {
+        for (original, parked) in self.set_aside.drain(..).rev() {
+            if original.query_exists(None::<&gio::Cancellable>) {
+                let _ = delete_tree(&parked, &gio::Cancellable::new());
+            } else {
+                let flags = gio::FileCopyFlags::NOFOLLOW_SYMLINKS;
+                let _ = parked.move_(&original, flags, None::<&gio::Cancellable>, None);
+            }
+        }
+    }
+
+    /// Keeps `target` and everything written below it, even if the item fails later on.
+    fn commit(&mut self, target: &gio::File) {
+        self.created.retain(|file| !file.equal(target) && !file.has_prefix(target));
+    }
+
+    fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            (self.merged, "merged"),
+            (self.skipped, "skipped"),
+            (self.replaced, "replaced"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(format!("Finished: {}", parts.join(", ")))
+        }
+    }
+
+    /// Ends a job that went through, with a word on anything unusual that happened.
+    fn finish(&self) {
+        if let Some(summary) = self.summary() {
+            self.send(Message::Summary(summary));
+        }
+        self.send(Message::Progress {
+            done: self.total_size,
+            total: self.total_size,
+        });
+        self.send(Message::Finished);
+    }
+
+    fn fail(&self, err: &glib::Error) {
+        self.send(if err.matches(gio::IOErrorEnum::Cancelled) {
+            Message::Cancelled
+        } else {
+            Message::Failed
+        });
+    }
+
+    /// The file already at `target`, looked up ignoring case on filesystems that do.
+    fn existing(&self, target: &gio::File) -> Option<gio::File> {
+        if target.query_exists(Some(self.cancellable)) {
+            return Some(target.clone());
+        }
+
+        let (parent, name) = (target.parent()?, target.basename()?);
+        let fs_type = parent
+            .query_filesystem_info("filesystem::type", Some(self.cancellable))
+            .ok()?
+            .attribute_string("filesystem::type")?;
+        if !CASE_INSENSITIVE_FILESYSTEMS.contains(&fs_type.as_str()) {
+            return None;
+        }
+
+        let name = name.to_string_lossy().to_lowercase();
+        let enumerator = parent
+            .enumerate_children(
+                "standard::name",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                Some(self.cancellable),
+            )
+            .ok()?;
+        while let Ok(Some(info)) = enumerator.next_file(Some(self.cancellable)) {
+            if info.name().to_string_lossy().to_lowercase() == name {
+                return Some(parent.child(info.name()));
+            }
+        }
+        None
+    }
+
+    /// Blocks until the user decides what to do with `src` colliding with `existing`.
+    ///
+    /// "Apply to all" is remembered separately for folders that could be merged
+    /// and for everything else, so answering one never decides the other.
+    fn ask(&mut self, src: &gio::File, existing: &gio::File, is_merge: bool) -> Resolution {
+        let remembered = if is_merge {
+            self.apply_to_all_folders
+        } else {
+            self.apply_to_all
+        };
+        if let Some(resolution) = remembered {
+            return resolution;
+        }
+
+        let (reply, answer) = mpsc::channel();
+        self.send(Message::Conflict(Conflict {
+            src: src.clone(),
+            existing: existing.clone(),
+            is_merge,
+            reply,
+        }));
+
+        let (resolution, apply_to_all) = self.wait_for(answer).unwrap_or((Resolution::Skip, false));
+        if apply_to_all && is_merge {
+            self.apply_to_all_folders = Some(resolution);
+        } else if apply_to_all {
+            self.apply_to_all = Some(resolution);
+        }
+        resolution
+    }
+
+    /// Blocks until the main thread answers, `None` once the job is cancelled.
+    fn wait_for<T>(&self, answer: mpsc::Receiver<T>) -> Option<T> {
+        // Keep an eye on the cancellable, nobody may ever answer once the job is cancelled.
+        loop {
+            match answer.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(answer) => return Some(answer),
+                Err(mpsc::RecvTimeoutError::Timeout) if !self.cancellable.is_cancelled() => (),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Runs `op` for `file` until it succeeds or the user gives up on it, `None` if skipped.
+    ///
+    /// Cancellation and "Abort" come back as errors and end the job.
+    fn attempt<T>(
+        &mut self,
+        file: &gio::File,
+        mut op: impl FnMut(&mut Self) -> Result<T, glib::Error>,
+    ) -> Result<Option<T>, glib::Error> {
+        loop {
+            let error = match op(self) {
+                Ok(value) => return Ok(Some(value)),
+                Err(err) if err.matches(gio::IOErrorEnum::Cancelled) => return Err(err),
+                Err(err) => err,
+            };
+
+            let action = if self.skip_all_errors {
+                ErrorAction::Skip
+            } else {
+                let (reply, answer) = mpsc::channel();
+                self.send(Message::Error(FileError {
+                    file: file.clone(),
+                    error: error.clone(),
+                    reply,
+                }));
+                self.wait_for(answer).unwrap_or(ErrorAction::Abort)
+            };
+            self.cancellable.set_error_if_cancelled()?;
+
+            match action {
+                ErrorAction::Retry => continue,
+                ErrorAction::Abort => return Err(error),
+                ErrorAction::SkipAll => self.skip_all_errors = true,
+                ErrorAction::Skip => (),
+            }
+            self.skipped += 1;
+            return Ok(None);
+        }
+    }
+
+    fn is_dir(&self, file: &gio::File) -> Result<bool, glib::Error> {
+        file.query_info(
+            "standard::type",
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            Some(self.cancellable),
+        )
+        .map(|info| info.file_type() == gio::FileType::Directory)
+    }
+
+    /// Where `src` should end up once a conflict at `target`, if any, is resolved.
+    fn destination(
+        &mut self,
+        src: &gio::File,
+        target: &gio::File,
+    ) -> Result<Destination, glib::Error> {
+        let flags = gio::FileCopyFlags::NOFOLLOW_SYMLINKS;
+
+        let existing = match self.existing(target) {
+            Some(existing) => existing,
+            None => return Ok(Destination::Create(target.clone(), flags)),
+        };
+
+        // Pasting an item onto itself can only ever mean a second copy of it.
+        let resolution = if existing.equal(src) {
+            Resolution::KeepBoth
+        } else {
+            let is_merge = self.is_dir(src)? && self.is_dir(&existing)?;
+            let resolution = self.ask(src, &existing, is_merge);
+            self.cancellable.set_error_if_cancelled()?;
+            resolution
+        };
+
+        Ok(match resolution {
+            Resolution::Skip => {
+                self.skipped += 1;
+                Destination::Skip
+            }
+            Resolution::Merge => {
+                self.merged += 1;
+                Destination::Merge(existing)
+            }
+            Resolution::KeepBoth => {
+                let dir = target.parent().expect("Error getting parent directory");
+                let name = target.basename().expect("Error getting file name");
+                Destination::Create(unique_child(&dir, &name.to_string_lossy()), flags)
+            }
+            Resolution::Replace => {
+                self.set_aside(&existing)?;
+                self.replaced += 1;
+                Destination::Replace(existing, flags)
+            }
+        })
+    }
+
+    /// Copies `src` to `target`, recreating the whole tree when `src` is a directory.
+    fn copy(&mut self, src: &gio::File, target: &gio::File) -> Result<(), glib::Error> {
+        self.pause.wait(self.cancellable);
+        let (target, flags, undoable) = match self.destination(src, target)? {
+            Destination::Create(target, flags) => {
+                let undoable = !self.is_created(target.parent());
+                (target, flags, undoable)
+            }
+            // Whatever was replaced is gone for good, so that can't be undone.
+            Destination::Replace(target, flags) => (target, flags, false),
+            Destination::Merge(target) => return self.copy_children(src, &target),
+            Destination::Skip => return Ok(()),
+        };
+
+        self.write(src, &target, flags)?;
+        if undoable && self.is_created(Some(target.clone())) {
+            self.completed.push((src.clone(), target));
+        }
+        Ok(())
+    }
+
+    /// Whether `file` was written by the item in flight.
+    fn is_created(&self, file: Option<gio::File>) -> bool {
+        file.map_or(false, |file| self.created.iter().any(|c| c.equal(&file)))
+    }
+
+    fn copy_children(&mut self, src: &gio::File, target: &gio::File) -> Result<(), glib::Error> {
+        let enumerator = match self.attempt(src, |t| {
+            src.enumerate_children(
+                "standard::name",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                Some(t.cancellable),
+            )
+        })? {
+            Some(enumerator) => enumerator,
+            None => return Ok(()),
+        };
+        while let Some(child) = enumerator.next_file(Some(self.cancellable))? {
+            self.copy(&src.child(child.name()), &target.child(child.name()))?;
+        }
+        Ok(())
+    }
+
+    /// Copies `src` to an already resolved `target`.
+    fn write(
+        &mut self,
+        src: &gio::File,
+        target: &gio::File,
+        flags: gio::FileCopyFlags,
+    ) -> Result<(), glib::Error> {
+        let info = match self.attempt(src, |t| {
+            src.query_info(
+                "standard::type,standard::size",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                Some(t.cancellable),
+            )
+        })? {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+
+        if info.file_type() == gio::FileType::Directory {
+            if self
+                .attempt(target, |t| target.make_directory(Some(t.cancellable)))?
+                .is_some()
+            {
+                self.created.push(target.clone());
+                self.copy_children(src, target)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(name) = src.basename() {
+            self.send(Message::CurrentFile(name.to_string_lossy().into_owned()));
+        }
+
+        let result = self.attempt(src, |t| t.copy_file(src, target, flags));
+        self.done += info.size();
+        result.map(|_| ())
+    }
+
+    /// Copies a single file, never leaving a truncated one behind.
+    fn copy_file(
+        &mut self,
+        src: &gio::File,
+        target: &gio::File,
+        flags: gio::FileCopyFlags,
+    ) -> Result<(), glib::Error> {
+        let result = src.copy(
+            target,
+            flags,
+            Some(self.cancellable),
+            Some(&mut |current, _total| self.report(current)),
+        );
+
+        // Don't touch a file that was already there though.
+        match &result {
+            Ok(()) => self.created.push(target.clone()),
+            Err(err) if !err.matches(gio::IOErrorEnum::Exists) => {
+                let _ = target.delete(None::<&gio::Cancellable>);
+            }
+            _ => (),
+        }
+
+        result
+    }
+
+    /// Moves `src` to `target`, renaming it when both share a filesystem.
+    ///
+    /// Across devices the tree is copied first and the source is only deleted once
+    /// the copy has completed, so a failure never loses the original.
+    fn move_(&mut self, src: &gio::File, target: &gio::File) -> Result<(), glib::Error> {
+        if target.equal(src) {
+            return Ok(());
+        }
+
+        self.pause.wait(self.cancellable);
+        let (target, flags, replaces) = match self.destination(src, target)? {
+            Destination::Create(target, flags) => (target, flags, false),
+            Destination::Replace(target, flags) => (target, flags, true),
+            Destination::Merge(target) => return self.move_children(src, &target),
+            Destination::Skip => return Ok(()),
+        };
+
+        if let Some(name) = src.basename() {
+            self.send(Message::CurrentFile(name.to_string_lossy().into_owned()));
+        }
+
+        let renamed = self.attempt(src, |t| {
+            match src.move_(
+                &target,
+                flags | gio::FileCopyFlags::NO_FALLBACK_FOR_MOVE,
+                Some(t.cancellable),
+                None,
+            ) {
+                Err(err)
+                    if err.matches(gio::IOErrorEnum::NotSupported)
+                        || err.matches(gio::IOErrorEnum::WouldRecurse) =>
+                {
+                    Ok(false)
+                }
+                result => result.map(|_| true),
+            }
+        })?;
+
+        let moved = match renamed {
+            Some(true) => true,
+            Some(false) => self.move_by_copy(src, &target, flags)?,
+            None => false,
+        };
+
+        if moved && !replaces {
+            self.completed.push((src.clone(), target));
+        }
+        Ok(())
+    }
+
+    /// Moves `src` to a target on another device, true when the source is gone.
+    fn move_by_copy(
+        &mut self,
+        src: &gio::File,
+        target: &gio::File,
+        flags: gio::FileCopyFlags,
+    ) -> Result<bool, glib::Error> {
+        // Anything skipped while copying still only exists at the source.
+        let skipped = self.skipped;
+        self.write(src, target, flags)?;
+        // Part of the source may be gone soon, from here on the copy is the only one.
+        self.commit(target);
+        Ok(self.skipped == skipped
+            && self
+                .attempt(src, |t| delete_tree(src, t.cancellable))?
+                .is_some())
+    }
+
+    /// Moves the content of the folder `src` into the existing folder `target`.
+    ///
+    /// `src` is only removed when everything in it was moved, anything skipped keeps it alive.
+    fn move_children(&mut self, src: &gio::File, target: &gio::File) -> Result<(), glib::Error> {
+        let enumerator = match self.attempt(src, |t| {
+            src.enumerate_children(
+                "standard::name",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                Some(t.cancellable),
+            )
+        })? {
+            Some(enumerator) => enumerator,
+            None => return Ok(()),
+        };
+        while let Some(child) = enumerator.next_file(Some(self.cancellable))? {
+            self.move_(&src.child(child.name()), &target.child(child.name()))?;
+        }
+
+        match src.delete(Some(self.cancellable)) {
+            Err(err) if err.matches(gio::IOErrorEnum::NotEmpty) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Deletes `file` and, when it is a folder, everything below it.
+    ///
+    /// A folder that still holds a skipped item is left alone.
+    fn delete(&mut self, file: &gio::File) -> Result<(), glib::Error> {
+        self.pause.wait(self.cancellable);
+        let info = match self.attempt(file, |t| {
+            file.query_info(
+                "standard::type,standard::size",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                Some(t.cancellable),
+            )
+        })? {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+
+        if let Some(name) = file.basename() {
+            self.send(Message::CurrentFile(name.to_string_lossy().into_owned()));
+        }
+
+        // Items in the trash can only be deleted whole, which takes their content along.
+        let size = match info.file_type() {
+            gio::FileType::Directory if trash::is_trash(file) => tree_size(file, self.cancellable),
+            gio::FileType::Directory => 0,
+            _ => info.size(),
+        };
+        let is_dir = info.file_type() == gio::FileType::Directory && !trash::is_trash(file);
+        if is_dir {
+            let skipped = self.skipped;
+            let enumerator = match self.attempt(file, |t| {
+                file.enumerate_children(
+                    "standard::name",
+                    gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                    Some(t.cancellable),
+                )
+            })? {
+                Some(enumerator) => enumerator,
+                None => return Ok(()),
+            };
+            while let Some(child) = enumerator.next_file(Some(self.cancellable))? {
+                self.delete(&file.child(child.name()))?;
+            }
+            if self.skipped > skipped {
+                return Ok(());
+            }
+        }
+
+        let deleted = self.attempt(file, |t| file.delete(Some(t.cancellable)))?;
+        if deleted.is_some() {
+            self.done += size;
+        }
+        self.report(0);
+        Ok(())
+    }
+}
+
+/// Deletes `file`, emptying it first when it is a directory.
+fn delete_tree(file: &gio::File, cancellable: &gio::Cancellable) -> Result<(), glib::Error> {
+    let info = file.query_info(
+        "standard::type",
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        Some(cancellable),
+    )?;
+
+    if info.file_type() == gio::FileType::Directory {
+        let mut below = Vec::new();
+        Walker::new(file).walk(cancellable, |batch, _| {
+            below.extend(batch.into_iter().map(|entry| entry.file));
+            Ok(())
+        })?;
+        // Everything in a folder was found after the folder itself.
+        for file in below.iter().rev() {
+            file.delete(Some(cancellable))?;
+        }
+    }
+
+    file.delete(Some(cancellable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::scratch_dir;
+
+    fn tree(root: &gio::File) {
+        root.child("folder/inner")
+            .make_directory_with_parents(None::<&gio::Cancellable>)
+            .expect("Error creating test folders");
+        for path in ["folder/a.txt", "folder/inner/b.txt"] {
+            root.child(path)
+                .replace_contents(
+                    b"content",
+                    None,
+                    false,
+                    gio::FileCreateFlags::NONE,
+                    None::<&gio::Cancellable>,
+                )
+                .expect("Error creating test file");
+        }
+    }
+
+    #[test]
+    fn committed_copy_survives_rollback() {
+        let root = scratch_dir("commit");
+        tree(&root);
+        let (src, target) = (root.child("folder"), root.child("copy"));
+        let (cancellable, pause) = (gio::Cancellable::new(), Pause::default());
+        let (sender, _receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+        let mut transfer = Transfer::new(&cancellable, &pause, &sender, 0);
+        transfer
+            .write(&src, &target, gio::FileCopyFlags::NOFOLLOW_SYMLINKS)
+            .expect("Error copying test folder");
+        transfer.commit(&target);
+        transfer.remove_created();
+
+        assert!(target
+            .child("inner/b.txt")
+            .query_exists(None::<&gio::Cancellable>));
+    }
+
+    #[test]
+    fn failed_replacement_brings_back_the_original() {
+        let root = scratch_dir("replace");
+        tree(&root);
+        let (src, existing) = (root.child("folder/a.txt"), root.child("folder/inner"));
+        let (cancellable, pause) = (gio::Cancellable::new(), Pause::default());
+        let (sender, _receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+        // A file taking the place of a folder, the way a failed item leaves it.
+        let mut transfer = Transfer::new(&cancellable, &pause, &sender, 0);
+        transfer.set_aside(&existing).expect("Error setting test folder aside");
+        transfer
+            .write(&src, &existing, gio::FileCopyFlags::NOFOLLOW_SYMLINKS)
+            .expect("Error copying test file");
+        transfer.remove_created();
+        transfer.restore_set_aside();
+
+        assert!(existing
+            .child("b.txt")
+            .query_exists(None::<&gio::Cancellable>));
+    }
+
+    #[test]
+    fn abort_while_deleting_source_loses_nothing() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = scratch_dir("abort");
+        tree(&root);
+        let (src, target) = (root.child("folder"), root.child("moved"));
+        // Nothing can be deleted from a read-only folder, so removing the source fails.
+        let inner = src.child("inner").path().expect("Error getting test path");
+        let read_only = std::fs::Permissions::from_mode(0o555);
+        std::fs::set_permissions(&inner, read_only).expect("Error protecting test folder");
+
+        let context = glib::MainContext::new();
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        receiver.attach(Some(&context), |message| {
+            if let Message::Error(error) = message {
+                let _ = error.reply.send(ErrorAction::Abort);
+            }
+            glib::Continue(true)
+        });
+
+        let job = {
+            let (src, target) = (src.clone(), target.clone());
+            std::thread::spawn(move || {
+                let (cancellable, pause) = (gio::Cancellable::new(), Pause::default());
+                let mut transfer = Transfer::new(&cancellable, &pause, &sender, 0);
+                let flags = gio::FileCopyFlags::NOFOLLOW_SYMLINKS;
+                let _ = transfer.move_by_copy(&src, &target, flags);
+                // What `transfer` does with an item that failed.
+                transfer.remove_created();
+            })
+        };
+        while !job.is_finished() {
+            context.iteration(false);
+        }
+        job.join().expect("Error joining test job");
+        let writable = std::fs::Permissions::from_mode(0o755);
+        std::fs::set_permissions(&inner, writable).expect("Error unprotecting test folder");
+
+        // Root may delete the source all the same, either way every file is still somewhere.
+        for path in ["a.txt", "inner/b.txt"] {
+            assert!(
+                src.child(path).query_exists(None::<&gio::Cancellable>)
+                    || target.child(path).query_exists(None::<&gio::Cancellable>),
+                "{} was lost",
+                path
+            );
+        }
+        assert!(target
+            .child("inner/b.txt")
+            .query_exists(None::<&gio::Cancellable>));
+    }
+}
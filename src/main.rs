@@ -4,7 +4,13 @@ extern crate adw;
 extern crate gtk;
 
 mod application;
+mod clipboard;
+mod dnd;
+mod query;
+mod search;
+mod trash;
 mod utilities;
+mod walker;
 mod window;
 
 #[path = "browser-view.rs"]
@@ -22,6 +28,15 @@ mod progress_info_model;
 #[path = "process-item-view.rs"]
 mod process_item_view;
 
+#[path = "file-operation.rs"]
+mod file_operation;
+
+#[path = "undo-stack.rs"]
+mod undo_stack;
+
+#[path = "batch-rename.rs"]
+mod batch_rename;
+
 use application::Application;
 use gtk::prelude::*;
 
@@ -29,6 +29,28 @@ mod imp {
         pub progressbar: TemplateChild<gtk::ProgressBar>,
         #[template_child]
         pub msg: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub file: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub status: TemplateChild<gtk::Label>,
+        #[template_child(id = "error-log")]
+        pub error_log: TemplateChild<gtk::Label>,
+        #[template_child(id = "error-box")]
+        pub error_box: TemplateChild<gtk::Box>,
+        #[template_child(id = "retry-btn")]
+        pub retry_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "skip-btn")]
+        pub skip_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "skip-all-btn")]
+        pub skip_all_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "abort-btn")]
+        pub abort_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "up-btn")]
+        pub up_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "start-btn")]
+        pub start_btn: TemplateChild<gtk::Button>,
+        #[template_child(id = "pause-btn")]
+        pub pause_btn: TemplateChild<gtk::ToggleButton>,
         #[template_child(id = "cancel-btn")]
         pub cancel_btn: TemplateChild<gtk::Button>,
     }
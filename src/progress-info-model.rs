@@ -1,24 +1,272 @@
-use gtk::{self, glib};
+use gtk::subclass::prelude::*;
+use gtk::{self, gio, glib, prelude::*};
+use std::fmt;
+use std::time::Instant;
+
+use crate::file_operation::{ErrorAction, FileError};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "JobState")]
+pub enum JobState {
+    #[default]
+    Queued,
+    Running,
+    Paused,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// The work behind a queued job, run on a worker thread once the job starts.
+pub struct Job(Box<dyn FnOnce() + Send>);
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Job")
+    }
+}
 
 glib::wrapper! {
     pub struct ProgressInfoModel(ObjectSubclass<imp::ProgressInfoModel>);
 }
 
+impl ProgressInfoModel {
+    /// Weight of the newest sample in the smoothed transfer speed.
+    const SPEED_SMOOTHING: f64 = 0.3;
+
+    fn update_progress(&self, done: i64, total: i64) {
+        let imp = self.imp();
+        let now = Instant::now();
+
+        if let Some((then, before)) = imp.last_sample.replace(Some((now, done))) {
+            let elapsed = now.duration_since(then).as_secs_f64();
+            if elapsed > 0.0 && done >= before {
+                let sample = (done - before) as f64 / elapsed;
+                let speed = *imp.speed.borrow();
+                imp.speed.replace(if speed > 0.0 {
+                    speed + Self::SPEED_SMOOTHING * (sample - speed)
+                } else {
+                    sample
+                });
+            }
+        }
+
+        imp.bytes_done.replace(done);
+        imp.bytes_total.replace(total);
+        self.set_property(
+            "fraction",
+            if total > 0 { done as f64 / total as f64 } else { 1.0 },
+        );
+
+        self.notify("bytes-done");
+        self.notify("bytes-total");
+        self.notify("speed");
+        self.notify("eta");
+        self.notify("status");
+    }
+
+    pub fn state(&self) -> JobState {
+        *self.imp().state.borrow()
+    }
+
+    fn set_state(&self, state: JobState) {
+        self.set_property("state", state);
+        if !self.is_active() && self.imp().pending_error.borrow().is_some() {
+            self.resolve_error(ErrorAction::Abort);
+        }
+        if !self.is_active() {
+            self.close_conflict_dialog();
+        }
+        self.notify("is-active");
+        self.notify("paused");
+        self.notify("queued");
+        self.notify("started");
+        self.notify("status");
+    }
+
+    /// Queued, running and paused jobs still have work ahead of them.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.state(),
+            JobState::Queued | JobState::Running | JobState::Paused
+        )
+    }
+
+    /// Filesystems the job reads from or writes to, see `file_operation::devices`.
+    ///
+    /// `None` until they were looked up, the job can't start before that.
+    pub fn devices(&self) -> Option<Vec<String>> {
+        self.imp().devices.borrow().clone()
+    }
+
+    pub fn set_devices(&self, devices: Vec<String>) {
+        self.imp().devices.replace(Some(devices));
+    }
+
+    /// Hands the job its work, it stays queued until `start` is called.
+    pub fn set_job(&self, job: impl FnOnce() + Send + 'static) {
+        self.imp().job.replace(Some(Job(Box::new(job))));
+    }
+
+    /// Runs a queued job on its own thread.
+    pub fn start(&self) {
+        if self.state() != JobState::Queued {
+            return;
+        }
+        if let Some(Job(job)) = self.imp().job.take() {
+            self.set_state(JobState::Running);
+            std::thread::spawn(job);
+        }
+    }
+
+    /// Holds or releases the job, it picks up exactly where it stopped.
+    pub fn set_paused(&self, paused: bool) {
+        let state = match (self.state(), paused) {
+            (JobState::Running, true) => JobState::Paused,
+            (JobState::Paused, false) => JobState::Running,
+            _ => return,
+        };
+
+        self.imp().pause.set(paused);
+        // The time spent paused says nothing about the transfer speed.
+        self.imp().last_sample.replace(None);
+        self.set_state(state);
+    }
+
+    /// Items the job got to their target, as `(source, target)`, handed out once.
+    pub fn take_completed(&self) -> Vec<(gio::File, gio::File)> {
+        self.imp().completed.take()
+    }
+
+    /// Answers the error the job is waiting on.
+    pub fn resolve_error(&self, action: ErrorAction) {
+        if let Some(reply) = self.imp().pending_error.take() {
+            // The job may be gone already if it was cancelled in the meantime.
+            let _ = reply.send(action);
+        }
+        self.imp().error.replace(None);
+        self.notify("error");
+        self.notify("has-error");
+    }
+
+    /// The dialog asking about a conflict for the job, if one is open.
+    ///
+    /// It is closed once the job ends or goes away, nobody waits for its answer anymore.
+    pub fn set_conflict_dialog(&self, dialog: Option<&gtk::Dialog>) {
+        self.imp().conflict_dialog.replace(dialog.cloned());
+    }
+
+    fn close_conflict_dialog(&self) {
+        if let Some(dialog) = self.imp().conflict_dialog.take() {
+            dialog.response(gtk::ResponseType::Cancel);
+        }
+    }
+
+    fn push_error(&self, error: FileError) {
+        let description = error.description();
+        self.imp().errors.borrow_mut().push(description.clone());
+        self.imp().error.replace(Some(description));
+        self.imp().pending_error.replace(Some(error.reply));
+        self.notify("error");
+        self.notify("has-error");
+        self.notify("error-log");
+    }
+
+    /// Stops the job, what it already finished stays and the partial item is removed.
+    pub fn cancel(&self) {
+        match self.state() {
+            JobState::Queued => {
+                self.imp().job.take();
+                self.set_state(JobState::Cancelled);
+            }
+            JobState::Running | JobState::Paused => self.imp().cancellable.cancel(),
+            _ => (),
+        }
+    }
+
+    /// Seconds left at the current speed, `-1` while it can't be estimated.
+    pub fn eta(&self) -> i64 {
+        let imp = self.imp();
+        let speed = *imp.speed.borrow();
+        if speed <= 0.0 {
+            return -1;
+        }
+        ((*imp.bytes_total.borrow() - *imp.bytes_done.borrow()) as f64 / speed).ceil() as i64
+    }
+
+    /// Human readable byte counts, speed and time left.
+    pub fn status(&self) -> String {
+        let prefix = match self.state() {
+            JobState::Queued => return "Waiting for other jobs to finish".to_owned(),
+            JobState::Running => "",
+            JobState::Paused => "Paused — ",
+            JobState::Finished => return "Finished".to_owned(),
+            JobState::Failed => return "Failed".to_owned(),
+            JobState::Cancelled => return "Cancelled".to_owned(),
+        };
+
+        let imp = self.imp();
+        let mut status = format!(
+            "{}{} of {}",
+            prefix,
+            glib::format_size(*imp.bytes_done.borrow() as u64),
+            glib::format_size(*imp.bytes_total.borrow() as u64)
+        );
+
+        if self.state() == JobState::Paused {
+            return status;
+        }
+
+        let speed = *imp.speed.borrow();
+        if speed > 0.0 {
+            status.push_str(&format!(" — {}/s", glib::format_size(speed as u64)));
+        }
+
+        match self.eta() {
+            eta if eta < 0 => (),
+            eta if eta < 60 => status.push_str(&format!(", {} seconds left", eta)),
+            eta if eta < 3600 => status.push_str(&format!(", {} minutes left", eta / 60)),
+            eta => status.push_str(&format!(", {} hours left", eta / 3600)),
+        }
+
+        status
+    }
+}
+
 mod imp {
     use glib::*;
     use gtk::subclass::prelude::*;
     use gtk::{self, gio, glib};
     use std::cell::RefCell;
+    use std::sync::{mpsc, Arc};
+    use std::time::Instant;
+
+    use super::{Job, JobState};
+    use crate::file_operation::{ErrorAction, Message, Pause};
 
     #[derive(Debug)]
     pub struct ProgressInfoModel {
         pub msg: RefCell<Option<String>>,
-        pub sender: glib::Sender<f64>,
-        pub receiver: RefCell<Option<glib::Receiver<f64>>>,
+        pub state: RefCell<JobState>,
+        pub job: RefCell<Option<Job>>,
+        pub devices: RefCell<Option<Vec<String>>>,
+        pub sender: glib::Sender<Message>,
+        pub receiver: RefCell<Option<glib::Receiver<Message>>>,
         pub fraction: RefCell<f64>,
+        pub current_file: RefCell<Option<String>>,
+        pub error: RefCell<Option<String>>,
+        pub errors: RefCell<Vec<String>>,
+        pub completed: RefCell<Vec<(gio::File, gio::File)>>,
+        pub pending_error: RefCell<Option<mpsc::Sender<ErrorAction>>>,
+        pub conflict_dialog: RefCell<Option<gtk::Dialog>>,
+        pub bytes_done: RefCell<i64>,
+        pub bytes_total: RefCell<i64>,
+        pub speed: RefCell<f64>,
+        pub last_sample: RefCell<Option<(Instant, i64)>>,
         pub source: RefCell<Option<glib::Source>>,
         pub context: glib::MainContext,
         pub cancellable: gio::Cancellable,
+        pub pause: Arc<Pause>,
     }
 
     #[glib::object_subclass]
@@ -31,12 +279,26 @@ mod imp {
 
             Self {
                 msg: Default::default(),
+                state: Default::default(),
+                job: Default::default(),
+                devices: Default::default(),
                 sender,
                 receiver: RefCell::new(Some(receiver)),
                 fraction: RefCell::from(0.0),
+                current_file: Default::default(),
+                error: Default::default(),
+                errors: Default::default(),
+                completed: Default::default(),
+                pending_error: Default::default(),
+                conflict_dialog: Default::default(),
+                bytes_done: Default::default(),
+                bytes_total: Default::default(),
+                speed: Default::default(),
+                last_sample: Default::default(),
                 source: Default::default(),
                 context: glib::MainContext::default(),
                 cancellable: gio::Cancellable::new(),
+                pause: Default::default(),
             }
         }
     }
@@ -48,12 +310,27 @@ mod imp {
                 .borrow_mut()
                 .take()
                 .expect("Error getting receiver");
-            let fraction = &self.fraction;
 
             let sourceid = receiver.attach(
                 Some(&self.context),
-                clone!(@strong fraction, @strong obj => move |f| {
-                    obj.set_property("fraction", f);
+                clone!(@weak obj => @default-return glib::Continue(false), move |message| {
+                    match message {
+                        Message::Progress { done, total } => obj.update_progress(done, total),
+                        Message::CurrentFile(name) => obj.set_property("current-file", name),
+                        Message::Finished => obj.set_state(JobState::Finished),
+                        Message::Failed => obj.set_state(JobState::Failed),
+                        Message::Cancelled => obj.set_state(JobState::Cancelled),
+                        Message::Error(error) => obj.push_error(error),
+                        Message::Completed(items) => {
+                            obj.imp().completed.borrow_mut().extend(items);
+                        }
+                        Message::Summary(msg) => obj.set_property("msg", msg),
+                        Message::Conflict(conflict) => {
+                            glib::MainContext::default().spawn_local(
+                                crate::window::conflict_dialog(obj.downgrade(), conflict),
+                            );
+                        }
+                    }
                     glib::Continue(true)
                 }),
             );
@@ -83,6 +360,113 @@ mod imp {
                         None,
                         glib::ParamFlags::READWRITE,
                     ),
+                    glib::ParamSpecEnum::new(
+                        "state",
+                        "state",
+                        "state",
+                        JobState::static_type(),
+                        JobState::default() as i32,
+                        glib::ParamFlags::READWRITE,
+                    ),
+                    glib::ParamSpecBoolean::new(
+                        "is-active",
+                        "is-active",
+                        "is-active",
+                        true,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecBoolean::new(
+                        "queued",
+                        "queued",
+                        "queued",
+                        true,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecBoolean::new(
+                        "started",
+                        "started",
+                        "started",
+                        false,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecBoolean::new(
+                        "paused",
+                        "paused",
+                        "paused",
+                        false,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecString::new(
+                        "current-file",
+                        "current-file",
+                        "current-file",
+                        None,
+                        glib::ParamFlags::READWRITE,
+                    ),
+                    glib::ParamSpecString::new(
+                        "error",
+                        "error",
+                        "error",
+                        None,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecBoolean::new(
+                        "has-error",
+                        "has-error",
+                        "has-error",
+                        false,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecString::new(
+                        "error-log",
+                        "error-log",
+                        "error-log",
+                        None,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecInt64::new(
+                        "bytes-done",
+                        "bytes-done",
+                        "bytes-done",
+                        0,
+                        i64::MAX,
+                        0,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecInt64::new(
+                        "bytes-total",
+                        "bytes-total",
+                        "bytes-total",
+                        0,
+                        i64::MAX,
+                        0,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecDouble::new(
+                        "speed",
+                        "speed",
+                        "speed",
+                        0.0,
+                        f64::MAX,
+                        0.0,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecInt64::new(
+                        "eta",
+                        "eta",
+                        "eta",
+                        -1,
+                        i64::MAX,
+                        -1,
+                        glib::ParamFlags::READABLE,
+                    ),
+                    glib::ParamSpecString::new(
+                        "status",
+                        "status",
+                        "status",
+                        None,
+                        glib::ParamFlags::READABLE,
+                    ),
                 ]
             });
             PROPERTIES.as_ref()
@@ -106,22 +490,47 @@ mod imp {
                         self.msg.replace(value);
                     }
                 }
+                "current-file" => {
+                    if let Ok(value) = value.get() {
+                        self.current_file.replace(value);
+                    }
+                }
+                "state" => {
+                    if let Ok(value) = value.get() {
+                        self.state.replace(value);
+                    }
+                }
                 _ => unimplemented!(),
             }
         }
 
-        fn property(&self, _obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
             match pspec.name() {
                 "fraction" => self.fraction.borrow().to_value(),
                 "msg" => self.msg.borrow().to_value(),
+                "current-file" => self.current_file.borrow().to_value(),
+                "state" => self.state.borrow().to_value(),
+                "is-active" => obj.is_active().to_value(),
+                "queued" => (obj.state() == JobState::Queued).to_value(),
+                "started" => matches!(obj.state(), JobState::Running | JobState::Paused).to_value(),
+                "paused" => (obj.state() == JobState::Paused).to_value(),
+                "error" => self.error.borrow().to_value(),
+                "has-error" => self.error.borrow().is_some().to_value(),
+                "error-log" => self.errors.borrow().join("\n").to_value(),
+                "bytes-done" => self.bytes_done.borrow().to_value(),
+                "bytes-total" => self.bytes_total.borrow().to_value(),
+                "speed" => self.speed.borrow().to_value(),
+                "eta" => obj.eta().to_value(),
+                "status" => obj.status().to_value(),
                 _ => unimplemented!(),
             }
         }
 
-        fn dispose(&self, _obj: &Self::Type) {
+        fn dispose(&self, obj: &Self::Type) {
             if let Some(source) = self.source.borrow().as_ref() {
                 source.destroy()
             }
+            obj.close_conflict_dialog();
         }
     }
 }
@@ -0,0 +1,712 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{self, gio, glib};
+use regex::Regex;
+
+use crate::utilities;
+use crate::walker::{Entry, Walker};
+
+/// Kinds of items `type:` picks, by content type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Image,
+    Document,
+    Audio,
+    Video,
+    Text,
+    Folder,
+}
+
+/// Content types that count as documents besides those starting with one of these.
+const DOCUMENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "application/epub",
+    "application/rtf",
+    "application/msword",
+    "application/vnd.ms-",
+    "application/vnd.oasis.opendocument",
+    "application/vnd.openxmlformats-officedocument",
+];
+
+impl Kind {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "image" | "images" => Kind::Image,
+            "document" | "documents" | "doc" => Kind::Document,
+            "audio" | "music" => Kind::Audio,
+            "video" | "videos" => Kind::Video,
+            "text" => Kind::Text,
+            "folder" | "folders" | "dir" => Kind::Folder,
+            _ => return None,
+        })
+    }
+
+    fn matches(self, info: &gio::FileInfo) -> bool {
+        if info.file_type() == gio::FileType::Directory {
+            return self == Kind::Folder;
+        }
+        let content_type = match info.content_type() {
+            Some(content_type) => content_type,
+            None => return false,
+        };
+        match self {
+            Kind::Image => content_type.starts_with("image/"),
+            Kind::Audio => content_type.starts_with("audio/"),
+            Kind::Video => content_type.starts_with("video/"),
+            Kind::Text => gio::content_type_is_a(&content_type, "text/plain"),
+            Kind::Document => DOCUMENT_TYPES.iter().any(|t| content_type.starts_with(t)),
+            Kind::Folder => false,
+        }
+    }
+}
+
+/// What a search is narrowed down to, everything is allowed by default.
+#[derive(Debug, Clone)]
+pub struct Filters {
+    /// Any of these, when there are some.
+    pub kinds: Vec<Kind>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    /// Modification time bounds, in seconds since the epoch.
+    pub modified_after: Option<i64>,
+    pub modified_before: Option<i64>,
+    /// Name patterns, from `name:` globs and `regex:`, the name has to match them all.
+    pub patterns: Vec<Regex>,
+    /// Whether hidden items are searched, `None` goes with the view.
+    pub hidden: Option<bool>,
+    /// Whether items ignored by a `.gitignore` are searched.
+    pub ignored: bool,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            kinds: Vec::new(),
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+            patterns: Vec::new(),
+            hidden: None,
+            ignored: true,
+        }
+    }
+}
+
+type Test = Box<dyn Fn(&gio::File, &gio::FileInfo) -> bool>;
+
+impl Filters {
+    /// Tests on the item itself, those don't stop a walk from going into a folder.
+    fn item_tests(&self) -> Vec<Test> {
+        let mut tests: Vec<Test> = Vec::new();
+
+        if !self.kinds.is_empty() {
+            let kinds = self.kinds.clone();
+            tests.push(Box::new(move |_, info| kinds.iter().any(|kind| kind.matches(info))));
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let (min, max) = (self.min_size, self.max_size);
+            tests.push(Box::new(move |_, info| {
+                info.file_type() != gio::FileType::Directory
+                    && min.map_or(true, |min| info.size() >= min)
+                    && max.map_or(true, |max| info.size() <= max)
+            }));
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let (after, before) = (self.modified_after, self.modified_before);
+            tests.push(Box::new(move |_, info| {
+                let modified = info.attribute_uint64("time::modified") as i64;
+                after.map_or(true, |after| modified >= after)
+                    && before.map_or(true, |before| modified <= before)
+            }));
+        }
+        for pattern in &self.patterns {
+            let pattern = pattern.clone();
+            tests.push(Box::new(move |_, info| pattern.is_match(&info.display_name())));
+        }
+        tests
+    }
+
+    /// Tests on where the item is, a folder failing them is left out with all it holds.
+    fn path_tests(&self, show_hidden: bool) -> Vec<Test> {
+        let mut tests: Vec<Test> = Vec::new();
+
+        if !self.hidden.unwrap_or(show_hidden) {
+            tests.push(Box::new(|_, info| !info.is_hidden()));
+        }
+        if !self.ignored {
+            let ignores = RefCell::new(Ignores::default());
+            tests.push(Box::new(move |file, info| {
+                let is_dir = info.file_type() == gio::FileType::Directory;
+                !ignores.borrow_mut().is_ignored(file, is_dir)
+            }));
+        }
+        tests
+    }
+
+    /// The filters as `gtk::Filter`s, for an `EveryFilter` over a listing.
+    ///
+    /// Hidden items are left to the view unless `hidden:no` asks to leave them out.
+    pub fn gtk_filters(&self) -> Vec<gtk::Filter> {
+        self.item_tests()
+            .into_iter()
+            .chain(self.path_tests(true))
+            .map(|test| {
+                gtk::CustomFilter::new(move |obj| {
+                    let info = match obj.downcast_ref::<gio::FileInfo>() {
+                        Some(info) => info,
+                        None => return false,
+                    };
+                    let file = utilities::file_of(info)
+                        .unwrap_or_else(|| gio::File::for_path(info.name()));
+                    test(&file, info)
+                })
+                .upcast::<gtk::Filter>()
+            })
+            .collect()
+    }
+
+    /// A walker over `dir` that only hands out items passing the filters and `include`.
+    pub fn walker(
+        &self,
+        dir: &gio::File,
+        show_hidden: bool,
+        include: impl Fn(&Entry) -> bool + 'static,
+    ) -> Walker {
+        let item_tests = self.item_tests();
+        let path_tests = self.path_tests(show_hidden);
+
+        Walker::new(dir)
+            .exclude(move |entry| !path_tests.iter().all(|test| test(&entry.file, &entry.info)))
+            .include(move |entry| {
+                item_tests.iter().all(|test| test(&entry.file, &entry.info)) && include(entry)
+            })
+    }
+}
+
+/// What was typed in the search entry, filters taken apart from the words to look for.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub text: String,
+    pub filters: Filters,
+}
+
+impl Query {
+    /// Reads `type:image size:>10M modified:<7d report` like queries.
+    ///
+    /// Filters are `type:` (image, document, audio, video, text or folder), `size:` and
+    /// `modified:` with `<`, `>` or a `from..to` range, `name:` with a glob, `regex:`,
+    /// `hidden:` and `ignored:` with yes or no. Anything else is looked for in names,
+    /// double quotes keep words together, spaces and colons included.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut query = Self::default();
+        let mut words = Vec::new();
+
+        for token in tokens(input) {
+            let (key, value) = match token.split_once(':') {
+                Some((key, value)) if !value.is_empty() && !key.contains('"') => {
+                    (key.to_lowercase(), value.replace('"', ""))
+                }
+                _ => {
+                    words.push(token.replace('"', ""));
+                    continue;
+                }
+            };
+            let value = value.as_str();
+            let filters = &mut query.filters;
+            match key.as_str() {
+                "type" => {
+                    for kind in value.split(',') {
+                        filters.kinds.push(
+                            Kind::parse(&kind.to_lowercase())
+                                .ok_or_else(|| format!("Unknown type “{}”", kind))?,
+                        );
+                    }
+                }
+                "size" => {
+                    let (min, max) = range(value, parse_size)
+                        .ok_or_else(|| format!("Unknown size “{}”", value))?;
+                    filters.min_size = min.or(filters.min_size);
+                    filters.max_size = max.or(filters.max_size);
+                }
+                "modified" => {
+                    let (after, before) = modified_range(value)
+                        .ok_or_else(|| format!("Unknown date “{}”", value))?;
+                    filters.modified_after = after.or(filters.modified_after);
+                    filters.modified_before = before.or(filters.modified_before);
+                }
+                "name" => filters.patterns.push(
+                    Regex::new(&format!("(?i)^{}$", glob_regex(value)))
+                        .map_err(|err| err.to_string())?,
+                ),
+                "regex" => filters
+                    .patterns
+                    .push(Regex::new(value).map_err(|err| err.to_string())?),
+                "hidden" => filters.hidden = Some(yes(value)?),
+                "ignored" => filters.ignored = yes(value)?,
+                _ => words.push(token.replace('"', "")),
+            }
+        }
+
+        words.retain(|word| !word.is_empty());
+        query.text = words.join(" ");
+        Ok(query)
+    }
+}
+
+/// `input` split at whitespace outside double quotes, the quotes are kept.
+fn tokens(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in input.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if c.is_whitespace() && !quoted {
+            if let Some(start) = start.take() {
+                tokens.push(&input[start..i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&input[start..]);
+    }
+    tokens
+}
+
+fn yes(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" | "1" => Ok(true),
+        "no" | "false" | "0" => Ok(false),
+        _ => Err(format!("Expected yes or no, not “{}”", value)),
+    }
+}
+
+/// `>a`, `<b` or `a..b` as lower and upper bounds.
+fn range<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<(Option<T>, Option<T>)> {
+    if let Some(min) = value.strip_prefix('>') {
+        Some((Some(parse(min.trim_start_matches('='))?), None))
+    } else if let Some(max) = value.strip_prefix('<') {
+        Some((None, Some(parse(max.trim_start_matches('='))?)))
+    } else if let Some((min, max)) = value.split_once("..") {
+        Some((Some(parse(min)?), Some(parse(max)?)))
+    } else {
+        None
+    }
+}
+
+/// Sizes as in `10M`, `1.5GB` or `512KiB`, the `iB` ones counting in 1024s.
+fn parse_size(value: &str) -> Option<i64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let unit = unit.to_uppercase();
+    let (prefix, base) = match unit.strip_suffix("IB") {
+        Some(prefix) => (prefix, 1024f64),
+        None => (unit.strip_suffix('B').unwrap_or(&unit), 1000f64),
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    Some((number * base.powi(exponent)) as i64)
+}
+
+/// An age like `7d`, in seconds: `h`ours, `d`ays, `w`eeks, `m`onths or `y`ears.
+fn parse_age(value: &str) -> Option<i64> {
+    let unit = value.chars().last()?;
+    let number: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let unit = match unit {
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        'm' => 30 * 24 * 60 * 60,
+        'y' => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(number * unit)
+}
+
+/// A local date like `2022-05-31`, in seconds since the epoch.
+fn parse_date(value: &str) -> Option<i64> {
+    let mut parts = value.splitn(3, '-').map(|part| part.parse::<i32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    glib::DateTime::from_local(year, month, day, 0, 0, 0.0)
+        .ok()
+        .map(|date| date.to_unix())
+}
+
+/// Bounds of `modified:`, ages count back from now so `<7d` is the last week while
+/// dates are points in time so `<2022-01-01` is anything before that day.
+fn modified_range(value: &str) -> Option<(Option<i64>, Option<i64>)> {
+    if let Some(bounds) = range(value, parse_date) {
+        return Some(bounds);
+    }
+    let now = glib::DateTime::now_local().ok()?.to_unix();
+    // Being older than an age means being modified before that long ago.
+    let (min_age, max_age) = range(value, parse_age)?;
+    Some((max_age.map(|age| now - age), min_age.map(|age| now - age)))
+}
+
+/// `glob` as a regular expression, `*` and `?` stay within a path component, `**` doesn't.
+fn glob_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
+
+/// A line of a `.gitignore`.
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    negate: bool,
+    dir_only: bool,
+    /// Matched against the path from the `.gitignore`'s folder rather than the name.
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+
+        Some(Self {
+            regex: Regex::new(&format!("^{}$", glob_regex(pattern))).ok()?,
+            negate,
+            dir_only,
+            anchored,
+        })
+    }
+}
+
+/// The `.gitignore`s of the folders looked at so far.
+#[derive(Debug, Default)]
+struct Ignores {
+    /// Rules of each folder and whether it is the top of a repository.
+    folders: HashMap<PathBuf, (Rc<Vec<Rule>>, bool)>,
+}
+
+impl Ignores {
+    fn folder(&mut self, dir: &Path) -> (Rc<Vec<Rule>>, bool) {
+        self.folders
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let rules = std::fs::read_to_string(dir.join(".gitignore"))
+                    .map(|text| text.lines().filter_map(Rule::parse).collect())
+                    .unwrap_or_default();
+                (Rc::new(rules), dir.join(".git").exists())
+            })
+            .clone()
+    }
+
+    /// Whether git ignores `file`, nothing is ignored outside a repository.
+    fn is_ignored(&mut self, file: &gio::File, is_dir: bool) -> bool {
+        let path = match file.path() {
+            Some(path) => path,
+            None => return false,
+        };
+        if path.file_name().map_or(false, |name| name == ".git") {
+            return true;
+        }
+
+        // Folders from the top of the repository down to the one holding `file`.
+        let mut dirs = Vec::new();
+        let mut in_repository = false;
+        for dir in path.ancestors().skip(1) {
+            let (rules, is_top) = self.folder(dir);
+            dirs.push((dir, rules));
+            if is_top {
+                in_repository = true;
+                break;
+            }
+        }
+        if !in_repository {
+            return false;
+        }
+
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let mut ignored = false;
+        for (dir, rules) in dirs.iter().rev() {
+            let relative = match path.strip_prefix(dir) {
+                Ok(relative) => relative.to_string_lossy(),
+                Err(_) => continue,
+            };
+            for rule in rules.iter().filter(|rule| is_dir || !rule.dir_only) {
+                let subject = if rule.anchored { &relative } else { &name };
+                if rule.regex.is_match(subject) {
+                    ignored = !rule.negate;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+/// Chips in the filters popover, with the filter each one adds to the query.
+const CHIPS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Type",
+        &[
+            ("Images", "type:image"),
+            ("Documents", "type:document"),
+            ("Audio", "type:audio"),
+            ("Videos", "type:video"),
+            ("Text", "type:text"),
+            ("Folders", "type:folder"),
+        ],
+    ),
+    (
+        "Size",
+        &[
+            ("Under 100 KB", "size:<100K"),
+            ("Over 10 MB", "size:>10M"),
+            ("Over 1 GB", "size:>1G"),
+        ],
+    ),
+    (
+        "Modified",
+        &[
+            ("Today", "modified:<1d"),
+            ("This Week", "modified:<7d"),
+            ("This Month", "modified:<1m"),
+            ("Over a Year Ago", "modified:>1y"),
+        ],
+    ),
+    (
+        "Skip",
+        &[("Hidden", "hidden:no"), ("Ignored by Git", "ignored:no")],
+    ),
+];
+
+/// Takes `token` out of `text` or adds it at the end, keeping the rest as it is.
+fn toggle_token(text: &str, token: &str, active: bool) -> String {
+    let mut tokens: Vec<&str> = text.split_whitespace().filter(|t| *t != token).collect();
+    if active {
+        tokens.push(token);
+    }
+    tokens.join(" ")
+}
+
+/// A popover of filter chips for `entry`, each adds or removes its filter in the text
+/// and follows along when the filter is typed in by hand.
+pub fn chips_popover(entry: &gtk::SearchEntry) -> gtk::Popover {
+    let boxx = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(6)
+        .margin_top(6)
+        .margin_bottom(6)
+        .margin_start(6)
+        .margin_end(6)
+        .build();
+
+    let chips: Rc<RefCell<Vec<(gtk::ToggleButton, &'static str)>>> = Rc::default();
+    for &(title, group) in CHIPS {
+        let label = gtk::Label::new(Some(title));
+        label.set_xalign(0.0);
+        label.add_css_class("dim-label");
+        boxx.append(&label);
+
+        let flow = gtk::FlowBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .column_spacing(6)
+            .row_spacing(6)
+            .max_children_per_line(4)
+            .build();
+        for &(name, token) in group.iter() {
+            let chip = gtk::ToggleButton::with_label(name);
+            chip.add_css_class("pill");
+            chip.connect_toggled(glib::clone!(@weak entry => move |chip| {
+                let text = toggle_token(&entry.text(), token, chip.is_active());
+                if text != entry.text().as_str() {
+                    entry.set_text(&text);
+                    entry.set_position(-1);
+                }
+            }));
+            flow.insert(&chip, -1);
+            chips.borrow_mut().push((chip, token));
+        }
+        boxx.append(&flow);
+    }
+
+    entry.connect_changed(glib::clone!(@strong chips => move |entry| {
+        let text = entry.text();
+        for (chip, token) in chips.borrow().iter() {
+            chip.set_active(text.split_whitespace().any(|t| t == *token));
+        }
+    }));
+
+    let popover = gtk::Popover::new();
+    popover.set_child(Some(&boxx));
+    popover
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::scratch_dir;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn sizes() {
+        let filters = Query::parse("size:>10M").expect("Error parsing query").filters;
+        assert_eq!((filters.min_size, filters.max_size), (Some(10_000_000), None));
+
+        let filters = Query::parse("size:<=512KiB").expect("Error parsing query").filters;
+        assert_eq!((filters.min_size, filters.max_size), (None, Some(512 * 1024)));
+
+        let filters = Query::parse("size:1.5kb..2G").expect("Error parsing query").filters;
+        assert_eq!((filters.min_size, filters.max_size), (Some(1500), Some(2_000_000_000)));
+
+        assert!(Query::parse("size:>10X").is_err());
+        assert!(Query::parse("size:10M").is_err());
+    }
+
+    #[test]
+    fn modified() {
+        let now = glib::DateTime::now_local().expect("Error getting time").to_unix();
+        let filters = Query::parse("modified:<7d").expect("Error parsing query").filters;
+        let after = filters.modified_after.expect("Error reading lower bound");
+        assert!((after - (now - 7 * DAY)).abs() < 60);
+        assert_eq!(filters.modified_before, None);
+
+        let filters = Query::parse("modified:>1y").expect("Error parsing query").filters;
+        assert_eq!(filters.modified_after, None);
+        assert!(filters.modified_before.expect("Error reading upper bound") < now - 364 * DAY);
+
+        let filters = Query::parse("modified:2022-01-01..2022-01-31")
+            .expect("Error parsing query")
+            .filters;
+        let (after, before) = (filters.modified_after, filters.modified_before);
+        assert_eq!(before.zip(after).map(|(before, after)| before - after), Some(30 * DAY));
+
+        assert!(Query::parse("modified:<7x").is_err());
+        assert!(Query::parse("modified:<2022-13-01").is_err());
+    }
+
+    #[test]
+    fn unknown_tokens_are_words() {
+        let query =
+            Query::parse("annual color:blue report: type:image").expect("Error parsing query");
+        assert_eq!(query.text, "annual color:blue report:");
+        assert_eq!(query.filters.kinds, [Kind::Image]);
+
+        assert!(Query::parse("type:spreadsheet").is_err());
+        assert!(Query::parse("hidden:maybe").is_err());
+    }
+
+    #[test]
+    fn quoted_words() {
+        let query = Query::parse(r#""annual  report" "size:>10M" type:text"#)
+            .expect("Error parsing query");
+        assert_eq!(query.text, "annual  report size:>10M");
+        assert_eq!(query.filters.min_size, None);
+        assert_eq!(query.filters.kinds, [Kind::Text]);
+
+        let query = Query::parse(r#"name:"my file*""#).expect("Error parsing query");
+        assert!(query.text.is_empty());
+        assert!(query.filters.patterns[0].is_match("My File (2).txt"));
+    }
+
+    #[test]
+    fn name_globs_match_the_whole_name() {
+        let filters = Query::parse("name:*.rs").expect("Error parsing query").filters;
+        let pattern = &filters.patterns[0];
+        assert!(pattern.is_match("main.rs"));
+        assert!(pattern.is_match("MAIN.RS"));
+        assert!(!pattern.is_match("main.rs.orig"));
+        assert!(!pattern.is_match("main.rsx"));
+
+        let filters = Query::parse("name:IMG_????.[jp]*").expect("Error parsing query").filters;
+        assert!(filters.patterns[0].is_match("img_0042.jpg"));
+        assert!(!filters.patterns[0].is_match("img_42.jpg"));
+    }
+
+    #[test]
+    fn gitignore() {
+        let repository = scratch_dir("gitignore");
+        let root = repository.path().expect("Error getting test path");
+        std::fs::create_dir(root.join(".git")).expect("Error creating test repository");
+        std::fs::write(root.join(".gitignore"), "# logs\n*.log\n!keep.log\nbuild/\n/target\n")
+            .expect("Error writing test .gitignore");
+        std::fs::create_dir(root.join("sub")).expect("Error creating test folder");
+        std::fs::write(root.join("sub/.gitignore"), "docs/*.md\n")
+            .expect("Error writing test .gitignore");
+
+        let mut ignores = Ignores::default();
+        let mut ignored = |path: &str, is_dir| ignores.is_ignored(&repository.child(path), is_dir);
+
+        assert!(ignored("debug.log", false));
+        assert!(ignored("sub/deep/debug.log", false));
+        // Negation, the last matching rule wins.
+        assert!(!ignored("keep.log", false));
+        // Directory only.
+        assert!(ignored("build", true));
+        assert!(ignored("sub/build", true));
+        assert!(!ignored("build", false));
+        // Anchored to the folder of the `.gitignore`.
+        assert!(ignored("target", true));
+        assert!(!ignored("sub/target", true));
+        assert!(ignored("sub/docs/notes.md", false));
+        assert!(!ignored("docs/notes.md", false));
+        assert!(!ignored("sub/other/docs/notes.md", false));
+
+        assert!(ignored(".git", true));
+        assert!(!Ignores::default().is_ignored(&gio::File::for_path("/debug.log"), false));
+    }
+}
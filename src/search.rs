@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::{self, gio, glib};
+
+use crate::query::Query;
+use crate::walker::Progress;
+
+/// Results are sent in batches at most this often, so the list doesn't redraw for every hit.
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a hit needs on top of what the walker always asks for.
+const ATTRIBUTES: &str = "standard::display-name,standard::content-type,time::modified";
+
+/// Where the matching lines of a content search are kept on a hit's `FileInfo`.
+pub const MATCHES_ATTRIBUTE: &str = "search::matches";
+
+/// Matching lines shown for each file, and lines shown around each of them.
+const MAX_MATCHES: usize = 3;
+const CONTEXT_LINES: usize = 1;
+/// Longest line shown, the rest is cut off.
+const MAX_LINE_CHARS: usize = 200;
+/// Longest line looked into, and how much of a file is read at most.
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+const MAX_FILE_BYTES: u64 = 32 * 1024 * 1024;
+/// A NUL in the start of a file marks it as binary, whatever its content type says.
+const BINARY_CHECK_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Names,
+    Contents,
+}
+
+/// A line of a file shown in the results, `is_match` is false for context.
+#[derive(Debug)]
+pub struct Line {
+    pub number: usize,
+    pub text: String,
+    pub is_match: bool,
+}
+
+/// A search result as found on the search thread, `FileInfo` can't cross threads.
+#[derive(Debug)]
+pub struct Hit {
+    pub file: gio::File,
+    pub display_name: String,
+    pub file_type: gio::FileType,
+    pub size: i64,
+    /// Seconds since the epoch.
+    pub modified: u64,
+    pub content_type: Option<String>,
+    /// Lines found by a content search, with context.
+    pub lines: Vec<Line>,
+}
+
+impl Hit {
+    fn new(file: gio::File, info: &gio::FileInfo) -> Self {
+        Self {
+            file,
+            display_name: info.display_name().to_string(),
+            file_type: info.file_type(),
+            size: info.size(),
+            modified: info.attribute_uint64("time::modified"),
+            content_type: info.content_type().map(|c| c.to_string()),
+            lines: Vec::new(),
+        }
+    }
+
+    /// The hit as the views show it, the same attributes a `gtk::DirectoryList` fills in.
+    pub fn file_info(&self) -> gio::FileInfo {
+        let info = gio::FileInfo::new();
+        if let Some(name) = self.file.basename() {
+            info.set_name(&name);
+        }
+        info.set_display_name(&self.display_name);
+        info.set_file_type(self.file_type);
+        info.set_size(self.size);
+        info.set_attribute_uint64("time::modified", self.modified);
+        if let Some(content_type) = &self.content_type {
+            info.set_content_type(content_type);
+            info.set_icon(&gio::content_type_get_icon(content_type));
+        }
+        info.set_attribute_object("standard::file", &self.file);
+        if !self.lines.is_empty() {
+            info.set_attribute_string(MATCHES_ATTRIBUTE, &self.matches());
+        }
+        info
+    }
+
+    /// The lines found, grep style, `…` stands for lines left out in between.
+    fn matches(&self) -> String {
+        let mut text = String::new();
+        let mut last = None;
+        for line in &self.lines {
+            if last.map_or(false, |last| line.number > last + 1) {
+                text.push_str("…\n");
+            }
+            let separator = if line.is_match { ':' } else { '-' };
+            text.push_str(&format!("{}{} {}\n", line.number, separator, line.text));
+            last = Some(line.number);
+        }
+        text.trim_end().to_owned()
+    }
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Hits(Vec<Hit>),
+    Progress(Progress),
+    Finished,
+    /// The folder searched couldn't be walked, with why.
+    Failed(String),
+}
+
+/// Ends a search, every search ends with either `Finished` or `Failed`.
+fn finish(sender: &glib::Sender<Message>, walked: Result<Progress, glib::Error>) {
+    let _ = sender.send(match walked {
+        Ok(_) => Message::Finished,
+        Err(err) => Message::Failed(err.message().to_owned()),
+    });
+}
+
+/// Looks for items below `dir` passing the filters of `query` whose name contains its
+/// text, ignoring case.
+///
+/// This is blocking and runs on a worker thread, hits are sent in batches through
+/// `sender` until everything was looked at or `cancellable` is triggered.
+pub fn search_names(
+    dir: &gio::File,
+    query: &Query,
+    show_hidden: bool,
+    cancellable: &gio::Cancellable,
+    sender: &glib::Sender<Message>,
+) {
+    let text = query.text.to_lowercase();
+    let mut hits = Vec::new();
+    let mut last_batch = Instant::now();
+
+    let walker = query
+        .filters
+        .walker(dir, show_hidden, move |entry| {
+            entry.info.display_name().to_lowercase().contains(&text)
+        })
+        .attributes(ATTRIBUTES);
+
+    let walked = walker.walk(cancellable, |batch, progress| {
+        hits.extend(batch.into_iter().map(|entry| Hit::new(entry.file, &entry.info)));
+        if last_batch.elapsed() >= BATCH_INTERVAL {
+            last_batch = Instant::now();
+            let _ = sender.send(Message::Hits(std::mem::take(&mut hits)));
+            let _ = sender.send(Message::Progress(*progress));
+        }
+        Ok(())
+    });
+
+    let _ = sender.send(Message::Hits(hits));
+    finish(sender, walked);
+}
+
+/// Reads the next line into `buffer`, at most `MAX_LINE_BYTES` of it.
+///
+/// The rest of a longer line is skipped a buffer at a time, it is never held in memory.
+fn read_line(reader: &mut impl BufRead, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
+    let read = reader.by_ref().take(MAX_LINE_BYTES).read_until(b'\n', buffer)?;
+    if read as u64 == MAX_LINE_BYTES && buffer.last() != Some(&b'\n') {
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            match chunk.iter().position(|&byte| byte == b'\n') {
+                Some(pos) => {
+                    reader.consume(pos + 1);
+                    break;
+                }
+                None => {
+                    let len = chunk.len();
+                    reader.consume(len);
+                }
+            }
+        }
+    }
+    Ok(read)
+}
+
+/// Lines of `file` containing `query`, already lowercased, with the lines around them.
+///
+/// Only the first `MAX_FILE_BYTES` are read, and files that turn out to be binary after
+/// all have no matches.
+fn grep(
+    file: &gio::File,
+    query: &str,
+    cancellable: &gio::Cancellable,
+) -> Result<Vec<Line>, glib::Error> {
+    let stream = file.read(Some(cancellable))?.into_read().take(MAX_FILE_BYTES);
+    let mut reader = BufReader::with_capacity(BINARY_CHECK_BYTES, stream);
+    if reader.fill_buf().map_or(true, |chunk| chunk.contains(&0)) {
+        return Ok(Vec::new());
+    }
+    let mut lines = Vec::new();
+    let mut before = VecDeque::with_capacity(CONTEXT_LINES + 1);
+    let mut after = 0;
+    let mut matches = 0;
+    let mut buffer = Vec::new();
+
+    for number in 1.. {
+        buffer.clear();
+        match read_line(&mut reader, &mut buffer) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(_) => break,
+        }
+        if buffer.contains(&0) {
+            return Ok(Vec::new());
+        }
+        if number % 1000 == 0 {
+            cancellable.set_error_if_cancelled()?;
+        }
+
+        let text = String::from_utf8_lossy(&buffer);
+        let text = text.trim_end();
+        let is_match = matches < MAX_MATCHES && text.to_lowercase().contains(query);
+        let line = Line {
+            number,
+            text: text.trim_start().chars().take(MAX_LINE_CHARS).collect(),
+            is_match,
+        };
+
+        if is_match {
+            lines.extend(before.drain(..));
+            lines.push(line);
+            matches += 1;
+            after = CONTEXT_LINES;
+        } else if after > 0 {
+            lines.push(line);
+            after -= 1;
+        } else if matches == MAX_MATCHES {
+            break;
+        } else {
+            before.push_back(line);
+            if before.len() > CONTEXT_LINES {
+                before.pop_front();
+            }
+        }
+    }
+
+    if matches == 0 {
+        lines.clear();
+    }
+    Ok(lines)
+}
+
+/// Looks for the text of `query` inside the text files below `dir` that pass its
+/// filters, ignoring case.
+///
+/// The walk runs on the calling thread and feeds the files to a few more threads that
+/// read them, blocking and reported like `search_names`.
+pub fn search_contents(
+    dir: &gio::File,
+    query: &Query,
+    show_hidden: bool,
+    cancellable: &gio::Cancellable,
+    sender: &glib::Sender<Message>,
+) {
+    let filters = &query.filters;
+    let query = query.text.to_lowercase();
+    let (files, queue) = mpsc::sync_channel::<Hit>(64);
+    let queue = Arc::new(Mutex::new(queue));
+
+    let threads = std::thread::available_parallelism().map_or(2, |n| n.get().min(8));
+    let workers: Vec<std::thread::JoinHandle<()>> = (0..threads)
+        .map(|_| {
+            let queue = queue.clone();
+            let query = query.clone();
+            let cancellable = cancellable.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || loop {
+                let hit = match queue.lock().map(|queue| queue.recv()) {
+                    Ok(Ok(hit)) => hit,
+                    _ => break,
+                };
+                // Keep taking files so the walk isn't stuck, they just aren't read anymore.
+                if cancellable.is_cancelled() {
+                    continue;
+                }
+                if let Ok(lines) = grep(&hit.file, &query, &cancellable) {
+                    if !lines.is_empty() {
+                        let _ = sender.send(Message::Hits(vec![Hit { lines, ..hit }]));
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut last_progress = Instant::now();
+    let walker = filters
+        .walker(dir, show_hidden, |entry| {
+            // Anything that isn't some kind of text is taken for binary and left alone.
+            !entry.is_dir()
+                && entry
+                    .info
+                    .content_type()
+                    .map_or(false, |content_type| gio::content_type_is_a(&content_type, "text/plain"))
+        })
+        .attributes(ATTRIBUTES);
+
+    let walked = walker.walk(cancellable, |batch, progress| {
+        for entry in batch {
+            files
+                .send(Hit::new(entry.file, &entry.info))
+                .map_err(|_| glib::Error::new(gio::IOErrorEnum::Cancelled, "Search stopped"))?;
+        }
+        if last_progress.elapsed() >= BATCH_INTERVAL {
+            last_progress = Instant::now();
+            let _ = sender.send(Message::Progress(*progress));
+        }
+        Ok(())
+    });
+
+    drop(files);
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    finish(sender, walked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::scratch_dir;
+
+    fn grep_text(name: &str, content: &[u8], query: &str) -> Vec<(usize, String)> {
+        let file = scratch_dir(name).child("file.txt");
+        std::fs::write(file.path().expect("Test file has no path"), content)
+            .expect("Error writing test file");
+        grep(&file, query, &gio::Cancellable::new())
+            .expect("Error reading test file")
+            .into_iter()
+            .filter(|line| line.is_match)
+            .map(|line| (line.number, line.text))
+            .collect()
+    }
+
+    #[test]
+    fn overlong_lines_are_cut_and_skipped() {
+        let mut content = b"first needle\n".to_vec();
+        content.extend(std::iter::repeat(b'x').take(MAX_LINE_BYTES as usize * 3));
+        content.extend(b" needle far out\nlast needle\n");
+
+        let found = grep_text("grep-long", &content, "needle");
+        assert_eq!(found, [(1, "first needle".to_owned()), (3, "last needle".to_owned())]);
+    }
+
+    #[test]
+    fn nul_in_the_first_chunk_means_binary() {
+        let mut content = b"needle\n".to_vec();
+        content.push(0);
+        assert!(grep_text("grep-binary", &content, "needle").is_empty());
+        assert_eq!(grep_text("grep-text", b"a\nneedle\n", "needle"), [(2, "needle".to_owned())]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::{self, gio, glib};
+
+use crate::file_operation;
+
+/// The gvfs view of every trash can, the home one and each volume's `.Trash-$uid`.
+pub const TRASH_URI: &str = "trash:///";
+
+const ATTRIBUTES: &str = "standard::name,trash::orig-path,trash::deletion-date";
+
+pub fn location() -> gio::File {
+    gio::File::for_uri(TRASH_URI)
+}
+
+pub fn is_trash(dir: &gio::File) -> bool {
+    dir.has_uri_scheme("trash")
+}
+
+/// Where a trashed item came from, read from its `.trashinfo`.
+pub fn original_path(info: &gio::FileInfo) -> Option<PathBuf> {
+    info.attribute_byte_string("trash::orig-path")
+        .map(|path| path.as_str().into())
+}
+
+pub fn deletion_date(info: &gio::FileInfo) -> Option<glib::DateTime> {
+    info.attribute_string("trash::deletion-date").and_then(|date| {
+        glib::DateTime::from_iso8601(&date, Some(&glib::TimeZone::local())).ok()
+    })
+}
+
+/// Line shown under trashed items, as in "From Documents, deleted 12/05/22 10:42".
+pub fn describe(info: &gio::FileInfo) -> Option<String> {
+    let from = original_path(info)?;
+    let folder = from
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .map_or_else(|| "/".into(), |name| name.to_string_lossy());
+
+    Some(match deletion_date(info).and_then(|date| date.format("%x %X").ok()) {
+        Some(date) => format!("From {}, deleted {}", folder, date),
+        None => format!("From {}", folder),
+    })
+}
+
+fn entries() -> Vec<gio::FileInfo> {
+    let trash = location();
+    let enumerator = match trash.enumerate_children(
+        ATTRIBUTES,
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        None::<&gio::Cancellable>,
+    ) {
+        Ok(enumerator) => enumerator,
+        Err(_) => return Vec::new(),
+    };
+
+    std::iter::from_fn(|| enumerator.next_file(None::<&gio::Cancellable>).ok().flatten()).collect()
+}
+
+/// The most recently trashed item that used to live at each of `originals`, as
+/// `(item, original)`, those not in the trash are left out.
+///
+/// The trash is listed once for all of them, this blocks.
+pub fn find(originals: &[gio::File]) -> Vec<(gio::File, gio::File)> {
+    let wanted: HashSet<PathBuf> = originals.iter().filter_map(|file| file.path()).collect();
+    let mut latest: HashMap<PathBuf, (Option<i64>, PathBuf)> = HashMap::new();
+    for info in entries() {
+        let path = match original_path(&info).filter(|path| wanted.contains(path)) {
+            Some(path) => path,
+            None => continue,
+        };
+        let date = deletion_date(&info).map(|date| date.to_unix());
+        if latest.get(&path).map_or(true, |(known, _)| date > *known) {
+            latest.insert(path, (date, info.name()));
+        }
+    }
+
+    let trash = location();
+    originals
+        .iter()
+        .filter_map(|original| {
+            let (_, name) = latest.get(&original.path()?)?;
+            Some((trash.child(name), original.clone()))
+        })
+        .collect()
+}
+
+/// Where a trashed item was deleted from, this blocks.
+pub fn original_location(item: &gio::File) -> Option<gio::File> {
+    let info = item
+        .query_info(
+            ATTRIBUTES,
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            None::<&gio::Cancellable>,
+        )
+        .ok()?;
+    original_path(&info).map(gio::File::for_path)
+}
+
+/// Pairs each trashed item in `items`, as `(item, original)`, with where it goes back
+/// to: where it was deleted from, or a new name there if that spot was taken since.
+///
+/// The folders the items came from may have been deleted as well, they are made again.
+/// This blocks.
+pub fn restore_targets(
+    items: Vec<(gio::File, gio::File)>,
+    cancellable: &gio::Cancellable,
+) -> Vec<(gio::File, gio::File)> {
+    items
+        .into_iter()
+        .map(|(item, original)| {
+            let target = match (original.parent(), original.basename()) {
+                (Some(dir), Some(name)) => {
+                    // Fails with `Exists` when the folder is still there.
+                    let _ = dir.make_directory_with_parents(Some(cancellable));
+                    if original.query_exists(Some(cancellable)) {
+                        file_operation::unique_child(&dir, &name.to_string_lossy())
+                    } else {
+                        original
+                    }
+                }
+                _ => original,
+            };
+            (item, target)
+        })
+        .collect()
+}
+
+/// Everything in every trash can, each item is deleted along with what is in it.
+pub fn items() -> Vec<gio::File> {
+    let trash = location();
+    entries()
+        .into_iter()
+        .map(|info| trash.child(info.name()))
+        .collect()
+}
@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+
+use std::path::PathBuf;
+
+use gtk::{self, gio};
+
+/// A file operation that already happened, with what it takes to reverse it.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Copies that were created, as `(source, copy)`.
+    Copy(Vec<(gio::File, gio::File)>),
+    /// Items that were moved, as `(from, to)`.
+    Move(Vec<(gio::File, gio::File)>),
+    CreateFolder(gio::File),
+    /// An empty file made from the "New Document" menu.
+    CreateFile(gio::File),
+    /// A new folder and the items that were moved into it, as `(from, to)`.
+    CreateFolderWith(gio::File, Vec<(gio::File, gio::File)>),
+    /// Symbolic links that were made, as `(what the link points to, link)`.
+    Link(Vec<(PathBuf, gio::File)>),
+    /// Items that were moved to the trash, by their original location.
+    Trash(Vec<gio::File>),
+    /// Files that were renamed together, as `(before, after)`.
+    Rename(Vec<(gio::File, gio::File)>),
+}
+
+impl Operation {
+    /// Short description used in toasts, as in "Undid copy of 3 items".
+    pub fn description(&self) -> String {
+        let items = |n: usize| {
+            if n == 1 {
+                "1 item".to_owned()
+            } else {
+                format!("{} items", n)
+            }
+        };
+
+        match self {
+            Operation::Copy(items_) => format!("copy of {}", items(items_.len())),
+            Operation::Move(items_) => format!("move of {}", items(items_.len())),
+            Operation::CreateFolder(_) => "new folder".to_owned(),
+            Operation::CreateFile(_) => "new document".to_owned(),
+            Operation::CreateFolderWith(_, moved) => {
+                format!("new folder with {}", items(moved.len()))
+            }
+            Operation::Link(links) => format!("making {} links", links.len()),
+            Operation::Trash(files) => format!("trashing of {}", items(files.len())),
+            Operation::Rename(files) if files.len() == 1 => "rename".to_owned(),
+            Operation::Rename(files) => format!("rename of {}", items(files.len())),
+        }
+    }
+}
+
+/// Application wide history of file operations.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    undo: RefCell<Vec<Operation>>,
+    redo: RefCell<Vec<Operation>>,
+}
+
+impl UndoStack {
+    /// Records a new operation, which makes everything that was undone before unreachable.
+    pub fn push(&self, operation: Operation) {
+        self.undo.borrow_mut().push(operation);
+        self.redo.borrow_mut().clear();
+    }
+
+    pub fn pop_undo(&self) -> Option<Operation> {
+        let operation = self.undo.borrow_mut().pop()?;
+        self.redo.borrow_mut().push(operation.clone());
+        Some(operation)
+    }
+
+    pub fn pop_redo(&self) -> Option<Operation> {
+        let operation = self.redo.borrow_mut().pop()?;
+        self.undo.borrow_mut().push(operation.clone());
+        Some(operation)
+    }
+}
@@ -1,33 +1,117 @@
-use gtk::{self, gio, glib};
+use std::path::{Component, Path, PathBuf};
 
-use gio::prelude::FileEnumeratorExt;
-use gio::prelude::FileExt;
-use gio::prelude::FileExtManual;
-use glib::clone;
+use gtk::{self, gio};
 
-pub trait Utilities {
-    fn iter<'a>(&'a self, store: &'a gio::ListStore);
+use gio::prelude::{Cast, FileExt};
+
+/// Why `name` can't be a file name anywhere, `None` when it can.
+pub fn name_error(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        Some("Name can't be empty")
+    } else if name == "." || name == ".." {
+        Some("\".\" and \"..\" are reserved names")
+    } else if name.contains('/') || name.contains('\0') {
+        Some("Names can't contain \"/\"")
+    } else if name.len() > 255 {
+        Some("Name is too long")
+    } else {
+        None
+    }
+}
+
+/// Why `name` can't be given to an item in `dir`, `None` when it can.
+///
+/// `current` is the item being renamed, keeping its own name is fine.
+pub fn new_name_error(
+    dir: &gio::File,
+    name: &str,
+    current: Option<&gio::File>,
+) -> Option<&'static str> {
+    name_error(name).or_else(|| {
+        let target = dir.child(name);
+        let taken = target.query_exists(None::<&gio::Cancellable>)
+            && !current.map_or(false, |current| current.equal(&target));
+        taken.then(|| "An item with this name already exists")
+    })
+}
+
+/// `name` split before its extension, as in `("notes", ".txt")`, a leading dot isn't one.
+pub fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(pos) if pos > 0 => name.split_at(pos),
+        _ => (name, ""),
+    }
+}
+
+/// The file a `FileInfo` from a listing or a search stands for.
+pub fn file_of(info: &gio::FileInfo) -> Option<gio::File> {
+    info.attribute_object("standard::file")
+        .and_then(|o| o.downcast::<gio::File>().ok())
+}
+
+/// `to` as seen from the directory `from`, both absolute, as in `../Music/song.ogg`.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    std::iter::repeat(Component::ParentDir)
+        .take(from.len() - common)
+        .chain(to[common..].iter().copied())
+        .collect()
+}
+
+/// An empty folder of its own for a test to play in.
+#[cfg(test)]
+pub fn scratch_dir(name: &str) -> gio::File {
+    let path = std::env::temp_dir().join(format!("files-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).expect("Error creating test folder");
+    gio::File::for_path(path)
 }
 
-impl Utilities for gio::File {
-    fn iter(&self, store: &gio::ListStore) {
-        self.enumerate_children_async(
-            "standard::*",
-            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
-            glib::PRIORITY_DEFAULT,
-            None::<&gio::Cancellable>,
-            clone!(@strong self as s, @strong store => move |result| {
-                if let Ok(enumrator) = result {
-                    while let Ok(Some(info)) = enumrator.next_file(None::<&gio::Cancellable>) {
-                        if info.file_type() == gio::FileType::Directory {
-                            let subdir = s.resolve_relative_path (info.name());
-                            subdir.iter(&store);
-                        } else {
-                            store.append(&info);
-                        }
-                    }
-                }
-            }),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        assert_eq!(split_extension("notes.txt"), ("notes", ".txt"));
+        assert_eq!(split_extension("archive.tar.gz"), ("archive.tar", ".gz"));
+        assert_eq!(split_extension("Makefile"), ("Makefile", ""));
+        assert_eq!(split_extension(".bashrc"), (".bashrc", ""));
+        assert_eq!(split_extension(".config.toml"), (".config", ".toml"));
+        assert_eq!(split_extension("trailing."), ("trailing", "."));
+    }
+
+    fn relative(from: &str, to: &str) -> PathBuf {
+        relative_path(Path::new(from), Path::new(to))
+    }
+
+    #[test]
+    fn relative_path_to_a_sibling() {
+        assert_eq!(relative("/home/me/Music", "/home/me/Pictures"), Path::new("../Pictures"));
+    }
+
+    #[test]
+    fn relative_path_to_a_nested_target() {
+        assert_eq!(
+            relative("/home/me", "/home/me/Music/album/song.ogg"),
+            Path::new("Music/album/song.ogg")
+        );
+    }
+
+    #[test]
+    fn relative_path_to_a_parent_folder() {
+        assert_eq!(
+            relative("/home/me/Music/album", "/home/me/notes.txt"),
+            Path::new("../../notes.txt")
         );
     }
+
+    #[test]
+    fn relative_path_without_common_folders() {
+        assert_eq!(relative("/home/me", "/mnt/disk/file"), Path::new("../../mnt/disk/file"));
+        assert_eq!(relative("/", "/etc/hosts"), Path::new("etc/hosts"));
+    }
 }
@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+
+use gtk::prelude::*;
+use gtk::{self, gio, glib};
+
+/// What the walker needs to know about every item, whatever else is asked for.
+const ATTRIBUTES: &str =
+    "standard::name,standard::type,standard::size,standard::is-hidden,standard::is-symlink,id::file";
+
+const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// An item found below the root of a walk.
+#[derive(Debug)]
+pub struct Entry {
+    pub file: gio::File,
+    pub info: gio::FileInfo,
+    /// 1 for the root's children, 2 for theirs and so on.
+    pub depth: usize,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool {
+        self.info.file_type() == gio::FileType::Directory
+    }
+}
+
+/// How far a walk got, sent along with every batch.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Progress {
+    pub folders: u64,
+    pub items: u64,
+    /// Bytes in all the items that aren't folders.
+    pub size: i64,
+    /// Folders that couldn't be listed, they are left out of the walk.
+    pub unreadable: u64,
+}
+
+type Predicate = Box<dyn Fn(&Entry) -> bool>;
+
+/// Walks everything below a folder, parents always come before what is in them.
+///
+/// This is blocking and is meant for worker threads. Items are handed out in batches
+/// every `batch_size` items looked at, and the walk stops as soon as the cancellable
+/// is triggered.
+pub struct Walker {
+    root: gio::File,
+    attributes: String,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    batch_size: usize,
+    include: Option<Predicate>,
+    exclude: Option<Predicate>,
+}
+
+impl Walker {
+    pub fn new(root: &gio::File) -> Self {
+        Self {
+            root: root.clone(),
+            attributes: ATTRIBUTES.to_owned(),
+            max_depth: None,
+            follow_symlinks: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            include: None,
+            exclude: None,
+        }
+    }
+
+    /// More attributes to query for each item, as in `"standard::content-type"`.
+    pub fn attributes(mut self, attributes: &str) -> Self {
+        self.attributes = format!("{},{}", ATTRIBUTES, attributes);
+        self
+    }
+
+    /// Doesn't go deeper than `depth`, 1 only lists the root.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Goes into symlinked folders, each folder is still only walked once.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Only hands out the items `include` accepts, folders are walked either way.
+    pub fn include(mut self, include: impl Fn(&Entry) -> bool + 'static) -> Self {
+        self.include = Some(Box::new(include));
+        self
+    }
+
+    /// Leaves out the items `exclude` accepts along with everything below them.
+    pub fn exclude(mut self, exclude: impl Fn(&Entry) -> bool + 'static) -> Self {
+        self.exclude = Some(Box::new(exclude));
+        self
+    }
+
+    /// Runs the walk, handing each batch to `f` along with the progress so far.
+    ///
+    /// An error from `f` stops the walk and is returned, so is cancellation. Folders
+    /// below the root that can't be listed are skipped.
+    pub fn walk(
+        self,
+        cancellable: &gio::Cancellable,
+        mut f: impl FnMut(Vec<Entry>, &Progress) -> Result<(), glib::Error>,
+    ) -> Result<Progress, glib::Error> {
+        let flags = if self.follow_symlinks {
+            gio::FileQueryInfoFlags::NONE
+        } else {
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS
+        };
+
+        let mut progress = Progress::default();
+        let mut batch = Vec::new();
+        let mut looked = 0;
+        // Folders already walked by `id::file`, a symlink pointing back up is walked once.
+        let mut visited = HashSet::new();
+        if let Some(id) = self
+            .root
+            .query_info("id::file", flags, Some(cancellable))?
+            .attribute_string("id::file")
+        {
+            visited.insert(id.to_string());
+        }
+
+        let mut dirs = vec![(self.root.clone(), 0)];
+        while let Some((dir, depth)) = dirs.pop() {
+            cancellable.set_error_if_cancelled()?;
+            let enumerator = match dir.enumerate_children(&self.attributes, flags, Some(cancellable)) {
+                Ok(enumerator) => enumerator,
+                Err(err) if err.matches(gio::IOErrorEnum::Cancelled) => return Err(err),
+                Err(err) if depth == 0 => return Err(err),
+                Err(_) => {
+                    progress.unreadable += 1;
+                    continue;
+                }
+            };
+            progress.folders += 1;
+
+            let mut subdirs = Vec::new();
+            loop {
+                let info = match enumerator.next_file(Some(cancellable)) {
+                    Ok(Some(info)) => info,
+                    Ok(None) => break,
+                    Err(err) if err.matches(gio::IOErrorEnum::Cancelled) || depth == 0 => {
+                        return Err(err)
+                    }
+                    Err(_) => {
+                        progress.unreadable += 1;
+                        break;
+                    }
+                };
+                let entry = Entry {
+                    file: dir.child(info.name()),
+                    info,
+                    depth: depth + 1,
+                };
+                if self.exclude.as_ref().map_or(false, |exclude| exclude(&entry)) {
+                    continue;
+                }
+
+                progress.items += 1;
+                if entry.is_dir() {
+                    let deeper = self.max_depth.map_or(true, |max| entry.depth < max);
+                    let id = entry.info.attribute_string("id::file");
+                    let first_visit = id.map_or(true, |id| visited.insert(id.to_string()));
+                    if deeper && first_visit {
+                        subdirs.push((entry.file.clone(), entry.depth));
+                    }
+                } else {
+                    progress.size += entry.info.size();
+                }
+
+                if self.include.as_ref().map_or(true, |include| include(&entry)) {
+                    batch.push(entry);
+                }
+                // Counted in items looked at, progress keeps coming when few are included.
+                looked += 1;
+                if looked >= self.batch_size {
+                    looked = 0;
+                    f(std::mem::take(&mut batch), &progress)?;
+                }
+            }
+
+            // Popped in listing order, so siblings come out in the order they were found.
+            dirs.extend(subdirs.into_iter().rev());
+        }
+
+        f(batch, &progress)?;
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::utilities::scratch_dir;
+
+    fn files(root: &gio::File, paths: &[&str]) {
+        let root = root.path().expect("Test folder has no path");
+        for path in paths {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().expect("Test file has no parent"))
+                .expect("Error creating test folder");
+            std::fs::write(path, "content").expect("Error writing test file");
+        }
+    }
+
+    /// The paths handed out below `root`, sorted, and the final progress.
+    fn walk(root: &gio::File, walker: Walker) -> (Vec<String>, Progress) {
+        let mut found = Vec::new();
+        let progress = walker
+            .walk(&gio::Cancellable::new(), |batch, _| {
+                found.extend(batch.iter().map(|entry| {
+                    let path = root.relative_path(&entry.file).expect("Entry outside the root");
+                    path.to_string_lossy().into_owned()
+                }));
+                Ok(())
+            })
+            .expect("Error walking test folder");
+        found.sort();
+        (found, progress)
+    }
+
+    #[test]
+    fn symlink_loops_are_walked_once() {
+        let root = scratch_dir("walker-loop");
+        files(&root, &["a/b.txt"]);
+        let path = root.path().expect("Test folder has no path");
+        std::os::unix::fs::symlink(&path, path.join("a/loop")).expect("Error creating symlink");
+
+        let (found, progress) = walk(&root, Walker::new(&root).follow_symlinks(true));
+        assert_eq!(found, ["a", "a/b.txt", "a/loop"]);
+        assert_eq!(progress.folders, 2);
+
+        let (found, progress) = walk(&root, Walker::new(&root));
+        assert_eq!(found, ["a", "a/b.txt", "a/loop"]);
+        assert_eq!(progress.folders, 2);
+    }
+
+    #[test]
+    fn max_depth_one_only_lists_the_root() {
+        let root = scratch_dir("walker-depth");
+        files(&root, &["a/b/c.txt", "d.txt"]);
+
+        let (found, progress) = walk(&root, Walker::new(&root).max_depth(1));
+        assert_eq!(found, ["a", "d.txt"]);
+        assert_eq!(progress.folders, 1);
+
+        let (found, _) = walk(&root, Walker::new(&root).max_depth(2));
+        assert_eq!(found, ["a", "a/b", "d.txt"]);
+    }
+
+    #[test]
+    fn unreadable_folders_are_skipped() {
+        let root = scratch_dir("walker-unreadable");
+        files(&root, &["locked/secret.txt", "open/x.txt"]);
+        let locked = root.path().expect("Test folder has no path").join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000))
+            .expect("Error locking test folder");
+        // Root reads it anyway, the walk has to get through either way.
+        let readable = std::fs::read_dir(&locked).is_ok();
+
+        let (found, progress) = walk(&root, Walker::new(&root));
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755))
+            .expect("Error unlocking test folder");
+
+        assert!(found.contains(&"locked".to_owned()));
+        assert!(found.contains(&"open/x.txt".to_owned()));
+        if !readable {
+            assert_eq!(progress.unreadable, 1);
+            assert!(!found.contains(&"locked/secret.txt".to_owned()));
+        }
+    }
+
+    #[test]
+    fn exclude_prunes_and_include_only_filters() {
+        let root = scratch_dir("walker-filters");
+        files(&root, &["skip/inner.txt", "keep/inner.txt", "top.txt"]);
+
+        let walker = Walker::new(&root)
+            .exclude(|entry| entry.info.name().as_os_str() == "skip")
+            .include(|entry| !entry.is_dir());
+        let (found, progress) = walk(&root, walker);
+        assert_eq!(found, ["keep/inner.txt", "top.txt"]);
+        assert_eq!(progress.folders, 2);
+        assert_eq!(progress.items, 3);
+    }
+
+    #[test]
+    fn batches_count_items_looked_at() {
+        let root = scratch_dir("walker-batches");
+        files(&root, &["1.txt", "2.txt", "3.txt", "4.txt", "keep.txt"]);
+
+        let mut batches = Vec::new();
+        Walker::new(&root)
+            .batch_size(2)
+            .include(|entry| entry.info.name().as_os_str() == "keep.txt")
+            .walk(&gio::Cancellable::new(), |batch, progress| {
+                batches.push((batch.len(), progress.items));
+                Ok(())
+            })
+            .expect("Error walking test folder");
+
+        // Two full batches while looking and whatever is left at the end.
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches.iter().map(|(len, _)| len).sum::<usize>(), 1);
+        assert_eq!(batches.iter().map(|(_, items)| *items).collect::<Vec<_>>(), [2, 4, 5]);
+    }
+
+    #[test]
+    fn cancelled_walks_stop() {
+        let root = scratch_dir("walker-cancel");
+        files(&root, &["a.txt"]);
+        let cancellable = gio::Cancellable::new();
+        cancellable.cancel();
+
+        let result = Walker::new(&root).walk(&cancellable, |_, _| Ok(()));
+        assert!(result.map_err(|err| err.matches(gio::IOErrorEnum::Cancelled)).unwrap_err());
+    }
+}
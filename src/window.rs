@@ -1,12 +1,19 @@
-use std::path::Path;
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
-use gtk::{self, gdk, gio, glib};
+use gtk::{self, gdk, gio, glib, glib::clone};
 
-use crate::application::ProcessType;
+use crate::application::{Application, ProcessType};
 use crate::browser_view::BrowserView;
+use crate::file_operation::{Conflict, Resolution};
+use crate::progress_info_model::ProgressInfoModel;
+use crate::undo_stack::Operation;
+use crate::utilities;
+use crate::walker::Walker;
 use crate::window;
 
 glib::wrapper! {
@@ -48,7 +55,14 @@ async fn appchooser_dialog(window: Rc<window::Window>, content_type: Rc<glib::GS
     }
 }
 
+/// Asks for a name and creates a folder with it in the folder the tab is showing.
 async fn dialog(window: Rc<window::Window>) {
+    let view = window.property::<BrowserView>("selected-page-child");
+    let dir = match view.property::<Option<gio::File>>("dir") {
+        Some(dir) => dir,
+        None => return,
+    };
+
     let boxx = gtk::Box::builder()
         .margin_top(8)
         .margin_bottom(8)
@@ -64,8 +78,16 @@ async fn dialog(window: Rc<window::Window>) {
 
     let entry = gtk::Entry::new();
     entry.set_hexpand(true);
+    entry.set_activates_default(true);
     boxx.append(&entry);
 
+    let error = gtk::Label::new(None);
+    error.set_xalign(0.0);
+    error.set_wrap(true);
+    error.add_css_class("error");
+    error.set_visible(false);
+    boxx.append(&error);
+
     let question_dialog = gtk::Dialog::builder()
         .transient_for(&*window)
         .modal(true)
@@ -79,17 +101,400 @@ async fn dialog(window: Rc<window::Window>) {
         ("Create", gtk::ResponseType::Ok),
     ]);
     question_dialog.set_default_response(gtk::ResponseType::Ok);
+    question_dialog.set_response_sensitive(gtk::ResponseType::Ok, false);
+
+    entry.connect_changed(clone!(@strong dir, @weak error, @weak question_dialog => move |entry| {
+        let reason = utilities::new_name_error(&dir, &entry.text(), None);
+        // An empty entry is where everyone starts, that's no reason to complain yet.
+        let shown = reason.filter(|_| !entry.text().is_empty());
+        error.set_label(shown.unwrap_or_default());
+        error.set_visible(shown.is_some());
+        question_dialog.set_response_sensitive(gtk::ResponseType::Ok, reason.is_none());
+    }));
+
+    // Stay open on failure, so the name can be fixed without typing it again.
+    let folder = loop {
+        if question_dialog.run_future().await != gtk::ResponseType::Ok {
+            question_dialog.close();
+            return;
+        }
 
-    let answer = question_dialog.run_future().await;
+        let folder = dir.child(entry.text().as_str());
+        match folder.make_directory(None::<&gio::Cancellable>) {
+            Ok(()) => break folder,
+            Err(err) => {
+                error.set_label(err.message());
+                error.set_visible(true);
+            }
+        }
+    };
     question_dialog.close();
 
-    if answer == gtk::ResponseType::Ok {
-        let cancellable = gio::Cancellable::new();
-        let dir: gio::File = window.property::<BrowserView>("selected-page-child").property("path");
-        dir.child(entry.text().as_str())
-            .make_directory(Some(&cancellable))
-            .expect("Error making directory");
+    if let Some(app) = window.application().and_then(|a| a.downcast::<Application>().ok()) {
+        app.record(Operation::CreateFolder(folder.clone()));
+    }
+    view.reveal(&folder);
+}
+
+/// How deep the New Document submenu goes into the Templates folder.
+const TEMPLATES_DEPTH: usize = 4;
+
+/// An entry of the New Document submenu, as found on the thread looking for templates.
+enum Template {
+    File {
+        label: String,
+        path: PathBuf,
+    },
+    Folder {
+        name: OsString,
+        label: String,
+        children: Vec<Template>,
+    },
+}
+
+impl Template {
+    fn label(&self) -> &str {
+        match self {
+            Self::File { label, .. } | Self::Folder { label, .. } => label,
+        }
+    }
+}
+
+/// The templates in the folder at `path` below the Templates folder, once it was found.
+fn templates_at<'a>(
+    templates: &'a mut Vec<Template>,
+    path: &Path,
+) -> Option<&'a mut Vec<Template>> {
+    path.iter().try_fold(templates, |templates, name| {
+        templates.iter_mut().find_map(|template| match template {
+            Template::Folder { name: folder, children, .. } if folder.as_os_str() == name => Some(children),
+            _ => None,
+        })
+    })
+}
+
+/// Everything below the Templates folder `dir` that isn't hidden.
+///
+/// This blocks. Symlinked folders are followed, but each folder is listed once and
+/// no deeper than `TEMPLATES_DEPTH`.
+fn find_templates(dir: &gio::File) -> Vec<Template> {
+    let mut templates = Vec::new();
+    let walker = Walker::new(dir)
+        .attributes("standard::display-name")
+        .follow_symlinks(true)
+        .max_depth(TEMPLATES_DEPTH)
+        .exclude(|entry| entry.info.is_hidden());
+
+    let _ = walker.walk(&gio::Cancellable::new(), |batch, _| {
+        for entry in batch {
+            // Folders come before what is in them, so the parent is already there.
+            let parent = dir
+                .relative_path(&entry.file)
+                .and_then(|path| path.parent().map(Path::to_path_buf));
+            let siblings = match parent.and_then(|parent| templates_at(&mut templates, &parent)) {
+                Some(siblings) => siblings,
+                None => continue,
+            };
+
+            let label = entry.info.display_name().to_string();
+            if entry.is_dir() {
+                let name = entry.info.name().into_os_string();
+                siblings.push(Template::Folder { name, label, children: Vec::new() });
+            } else if let Some(path) = entry.file.path() {
+                siblings.push(Template::File { label, path });
+            }
+        }
+        Ok(())
+    });
+    templates
+}
+
+/// Adds an entry for each of `templates` to `menu`, folders become submenus.
+fn append_templates(menu: &gio::Menu, mut templates: Vec<Template>) {
+    templates.sort_by_cached_key(|template| template.label().to_lowercase());
+
+    for template in templates {
+        match template {
+            Template::Folder { label, children, .. } => {
+                let submenu = gio::Menu::new();
+                append_templates(&submenu, children);
+                if submenu.n_items() > 0 {
+                    menu.append_submenu(Some(&label), &submenu);
+                }
+            }
+            Template::File { label, path } => {
+                // Templates are listed without their extension, the copy keeps it.
+                let (label, _) = utilities::split_extension(&label);
+                let item = gio::MenuItem::new(Some(label), None);
+                item.set_action_and_target_value(
+                    Some("new-document"),
+                    Some(&path.to_string_lossy().to_variant()),
+                );
+                menu.append_item(&item);
+            }
+        }
+    }
+}
+
+/// The "New Document" submenu, the templates in the XDG Templates folder are looked
+/// for on a thread and show up once they are found.
+fn templates_menu() -> gio::Menu {
+    let menu = gio::Menu::new();
+    let templates = gio::Menu::new();
+    if let Some(dir) = glib::user_special_dir(glib::UserDirectory::Templates) {
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let _ = sender.send(find_templates(&gio::File::for_path(dir)));
+        });
+        receiver.attach(
+            None,
+            clone!(@weak templates => @default-return glib::Continue(false), move |found| {
+                append_templates(&templates, found);
+                glib::Continue(false)
+            }),
+        );
+    }
+    menu.append_section(None, &templates);
+
+    let empty = gio::Menu::new();
+    let item = gio::MenuItem::new(Some("Empty File"), None);
+    item.set_action_and_target_value(Some("new-document"), Some(&"".to_variant()));
+    empty.append_item(&item);
+    menu.append_section(None, &empty);
+    menu
+}
+
+/// Asks before doing something that can't be undone, true when the user agreed.
+async fn confirm(window: &window::Window, title: &str, body: &str, accept: &str) -> bool {
+    let dialog = gtk::MessageDialog::builder()
+        .transient_for(window)
+        .modal(true)
+        .message_type(gtk::MessageType::Warning)
+        .text(title)
+        .secondary_text(body)
+        .build();
+
+    dialog.add_buttons(&[
+        ("Cancel", gtk::ResponseType::Cancel),
+        (accept, gtk::ResponseType::Accept),
+    ]);
+    if let Some(button) = dialog.widget_for_response(gtk::ResponseType::Accept) {
+        button.add_css_class("destructive-action");
+    }
+
+    let answer = dialog.run_future().await;
+    dialog.close();
+    answer == gtk::ResponseType::Accept
+}
+
+/// Asks before deleting `files` for good, listing every one of them.
+async fn confirm_delete(window: &window::Window, files: &[gio::File]) -> bool {
+    let names: Vec<String> = files
+        .iter()
+        .map(|file| file.parse_name().to_string())
+        .collect();
+
+    let list = gtk::Label::new(Some(&names.join("\n")));
+    list.set_xalign(0.0);
+    list.set_selectable(true);
+
+    let scrolled = gtk::ScrolledWindow::builder()
+        .child(&list)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .propagate_natural_height(true)
+        .max_content_height(200)
+        .build();
+
+    let dialog = gtk::MessageDialog::builder()
+        .transient_for(window)
+        .modal(true)
+        .message_type(gtk::MessageType::Warning)
+        .text(&format!("Permanently delete {} items?", files.len()))
+        .secondary_text("Deleted items can't be restored from the trash.")
+        .build();
+    dialog
+        .message_area()
+        .downcast::<gtk::Box>()
+        .expect("Error getting message area")
+        .append(&scrolled);
+
+    dialog.add_buttons(&[
+        ("Cancel", gtk::ResponseType::Cancel),
+        ("Delete", gtk::ResponseType::Accept),
+    ]);
+    if let Some(button) = dialog.widget_for_response(gtk::ResponseType::Accept) {
+        button.add_css_class("destructive-action");
+    }
+
+    let answer = dialog.run_future().await;
+    dialog.close();
+    answer == gtk::ResponseType::Accept
+}
+
+/// How the file `src` differs from `existing` in size and age, in a sentence.
+fn compare(src: &gio::FileInfo, existing: &gio::FileInfo) -> String {
+    let difference = glib::format_size((src.size() - existing.size()).unsigned_abs());
+    let size = match src.size().cmp(&existing.size()) {
+        Ordering::Equal => "the same size".to_owned(),
+        Ordering::Greater => format!("{} larger", difference),
+        Ordering::Less => format!("{} smaller", difference),
+    };
+    let modified = |info: &gio::FileInfo| info.attribute_uint64("time::modified");
+    let age = match modified(src).cmp(&modified(existing)) {
+        Ordering::Equal => "just as old",
+        Ordering::Greater => "newer",
+        Ordering::Less => "older",
+    };
+    format!("The replacement is {} and {}.", size, age)
+}
+
+/// Asks what to do about an item that already exists at a paste destination.
+///
+/// The dialog goes away by itself when the job of `model` ends first.
+pub async fn conflict_dialog(model: glib::WeakRef<ProgressInfoModel>, conflict: Conflict) {
+    if !model.upgrade().map_or(false, |model| model.is_active()) {
+        return;
     }
+
+    let query = |file: &gio::File| {
+        file.query_info(
+            "standard::type,standard::display-name,standard::size,time::modified",
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            None::<&gio::Cancellable>,
+        )
+        .ok()
+    };
+    let (src, existing) = (query(&conflict.src), query(&conflict.existing));
+
+    let is_dir = |info: &Option<gio::FileInfo>| {
+        info.as_ref()
+            .map_or(false, |i| i.file_type() == gio::FileType::Directory)
+    };
+    let describe = |info: &Option<gio::FileInfo>| {
+        info.as_ref().map_or_else(String::new, |i| {
+            let modified = i
+                .modification_date_time()
+                .and_then(|d| d.to_local().ok())
+                .and_then(|d| d.format("%c").ok())
+                .unwrap_or_default();
+            format!("{}, modified {}", glib::format_size(i.size() as u64), modified)
+        })
+    };
+
+    let boxx = gtk::Box::builder()
+        .margin_top(8)
+        .margin_bottom(8)
+        .margin_start(8)
+        .margin_end(8)
+        .spacing(4)
+        .orientation(gtk::Orientation::Vertical)
+        .build();
+
+    let label = gtk::Label::new(Some(&format!(
+        "{} named “{}” already exists in “{}”.",
+        if is_dir(&existing) { "A folder" } else { "A file" },
+        existing
+            .as_ref()
+            .map(|i| i.display_name().to_string())
+            .unwrap_or_default(),
+        conflict
+            .existing
+            .parent()
+            .and_then(|p| p.basename())
+            .map(|n| n.display().to_string())
+            .unwrap_or_default(),
+    )));
+    label.set_xalign(0.0);
+    label.set_wrap(true);
+    boxx.append(&label);
+
+    for (title, info) in [("Original", &existing), ("Replace with", &src)] {
+        let label = gtk::Label::new(Some(&format!("{}: {}", title, describe(info))));
+        label.set_xalign(0.0);
+        label.add_css_class("dim-label");
+        boxx.append(&label);
+    }
+
+    // Without meld, comparing tells which of the two is larger and newer.
+    let comparison = gtk::Label::new(None);
+    comparison.set_xalign(0.0);
+    comparison.set_wrap(true);
+    comparison.set_visible(false);
+    boxx.append(&comparison);
+
+    let apply_to_all = gtk::CheckButton::with_label(if conflict.is_merge {
+        "Apply this action to all conflicting folders"
+    } else {
+        "Apply this action to all conflicting files"
+    });
+    boxx.append(&apply_to_all);
+
+    let question_dialog = gtk::Dialog::builder()
+        .modal(true)
+        .use_header_bar(1)
+        .title("File Conflict")
+        .child(&boxx)
+        .build();
+    question_dialog.set_transient_for(
+        gio::Application::default()
+            .and_then(|app| app.downcast::<gtk::Application>().ok())
+            .and_then(|app| app.active_window())
+            .as_ref(),
+    );
+
+    let has_meld = glib::find_program_in_path("meld").is_some();
+    if !is_dir(&src) && !is_dir(&existing) {
+        question_dialog.add_button("Compare", gtk::ResponseType::Other(3));
+    }
+    question_dialog.add_buttons(&[
+        ("Skip", gtk::ResponseType::Other(1)),
+        ("Keep Both", gtk::ResponseType::Other(2)),
+        ("Replace", gtk::ResponseType::Other(0)),
+    ]);
+    if conflict.is_merge {
+        question_dialog.add_button("Merge", gtk::ResponseType::Other(4));
+        question_dialog.set_default_response(gtk::ResponseType::Other(4));
+    } else {
+        question_dialog.set_default_response(gtk::ResponseType::Other(0));
+    }
+    if let Some(model) = model.upgrade() {
+        model.set_conflict_dialog(Some(&question_dialog));
+    }
+
+    let resolution = loop {
+        match question_dialog.run_future().await {
+            gtk::ResponseType::Other(0) => break Resolution::Replace,
+            gtk::ResponseType::Other(2) => break Resolution::KeepBoth,
+            gtk::ResponseType::Other(4) => break Resolution::Merge,
+            gtk::ResponseType::Other(3) if !has_meld => {
+                if let (Some(src), Some(existing)) = (&src, &existing) {
+                    comparison.set_label(&compare(src, existing));
+                    comparison.set_visible(true);
+                }
+            }
+            gtk::ResponseType::Other(3) => {
+                let _ = gio::AppInfo::create_from_commandline(
+                    "meld",
+                    None,
+                    gio::AppInfoCreateFlags::NONE,
+                )
+                .and_then(|meld| {
+                    meld.launch(
+                        &[conflict.existing.clone(), conflict.src.clone()],
+                        None::<&gio::AppLaunchContext>,
+                    )
+                });
+            }
+            _ => break Resolution::Skip,
+        }
+    };
+    question_dialog.close();
+    if let Some(model) = model.upgrade() {
+        model.set_conflict_dialog(None);
+    }
+
+    // The job may already be gone if it was cancelled while we were asking.
+    let _ = conflict.reply.send((resolution, apply_to_all.is_active()));
 }
 
 impl Window {
@@ -101,6 +506,20 @@ impl Window {
         }
     }
 
+    /// Shows a short notification at the bottom of the window.
+    /// The New Document submenu, built the first time it is needed.
+    fn templates_menu(&self) -> gio::Menu {
+        self.imp()
+            .templates_menu
+            .borrow_mut()
+            .get_or_insert_with(templates_menu)
+            .clone()
+    }
+
+    pub fn add_toast(&self, msg: &str) {
+        self.imp().toast_overlay.add_toast(&adw::Toast::new(msg));
+    }
+
     pub fn new<P: glib::IsA<gtk::Application> + ToValue>(app: Option<&P>) -> Self {
         if let Some(app) = app {
             glib::Object::new(&[("application", &app)])
@@ -117,8 +536,12 @@ mod imp {
 
     use crate::application::Application;
     use crate::browser_view::BrowserView;
+    use crate::file_operation::{self, ErrorAction};
     use crate::progress_info_model::ProgressInfoModel;
+    use crate::query::{self, Query};
+    use crate::search;
     use crate::stack_button::AdwStackButton;
+    use crate::undo_stack::Operation;
 
     use glib::clone;
     
@@ -141,11 +564,13 @@ mod imp {
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(file = "window.ui")]
     pub struct Window {
+        #[template_child(id = "toast-overlay")]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
         #[template_child(id = "view-port")]
         pub view_port: TemplateChild<gtk::Box>,
 
         #[template_child(id = "tab-bar")]
-        pub tabbar: TemplateChild<adw::TabView>,
+        pub tabbar: TemplateChild<adw::TabBar>,
         #[template_child(id = "tab-view")]
         pub tabview: TemplateChild<adw::TabView>,
         #[template_child]
@@ -160,12 +585,20 @@ mod imp {
         pub single_selection: RefCell<Option<gio::FileInfo>>,
 
         pub selection: Rc<RefCell<Selection>>,
+        pub templates_menu: RefCell<Option<gio::Menu>>,
 
         pub view_type: RefCell<Option<String>>,
 
 
         #[template_child(id = "show-hidden-btn")]
         pub show_hidden_btn: TemplateChild<gtk::CheckButton>,
+
+        #[template_child(id = "entry_a")]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child(id = "content-search")]
+        pub content_search: TemplateChild<gtk::ToggleButton>,
+        #[template_child(id = "search-filters")]
+        pub search_filters: TemplateChild<gtk::MenuButton>,
     }
 
     #[glib::object_subclass]
@@ -248,24 +681,148 @@ mod imp {
             });
 
             klass.install_action("paste", None, |win, _name, _variant| {
-                win.property::<Application>("application").do_sync(
-                    super::ProcessType::Copy,
-                    &win.property("selected-view-path"),
-                );
+                let dir: gio::File = win
+                    .property::<BrowserView>("selected-page-child")
+                    .property("dir");
+                win.property::<Application>("application").paste(&dir);
             });
 
             klass.install_action("copy", None, |win, _name, _variant| {
-                if let Selection::Multi(selection) = &*win.imp().selection.borrow() {
-                    let app: Application = win.property("application");
-                    let store: gio::ListStore = app.property("selected-items-store");
+                let files = win.property::<BrowserView>("selected-page-child").selected_files();
+                if !files.is_empty() {
+                    win.property::<Application>("application")
+                        .set_selected_items(super::ProcessType::Copy, &files);
+                }
+            });
 
-                    store.remove_all();
-                    for item in selection.snapshot() {
-                        store.append(&item);
+            klass.install_action("cut", None, |win, _name, _variant| {
+                let files = win.property::<BrowserView>("selected-page-child").selected_files();
+                if !files.is_empty() {
+                    win.property::<Application>("application")
+                        .set_selected_items(super::ProcessType::Move, &files);
+                }
+            });
+
+            klass.install_action("trash", None, |win, _name, _variant| {
+                let view = win.property::<BrowserView>("selected-page-child");
+                // Items in the trash have nowhere further to go.
+                if view.is_trash() {
+                    let _ = win.activate_action("delete-permanently", None);
+                } else {
+                    win.property::<Application>("application").trash(&view.selected_files());
+                }
+            });
+
+            klass.install_action("rename", None, |win, _name, _variant| {
+                let view = win.property::<BrowserView>("selected-page-child");
+                match &view.selected_files()[..] {
+                    [] => (),
+                    [file] => view.start_rename(file),
+                    files => {
+                        gtk::glib::MainContext::default().spawn_local(
+                            crate::batch_rename::batch_rename_dialog(win.clone(), files.to_vec()),
+                        );
                     }
                 }
             });
 
+            // Takes the path of a template, or an empty string for an empty file.
+            klass.install_action("new-document", Some("s"), |win, _name, variant| {
+                let view = win.property::<BrowserView>("selected-page-child");
+                let (dir, template) = match (
+                    view.property::<Option<gio::File>>("dir"),
+                    variant.and_then(|v| v.get::<String>()),
+                ) {
+                    (Some(dir), Some(template)) => (dir, template),
+                    _ => return,
+                };
+                let app = win.property::<Application>("application");
+
+                let created = if template.is_empty() {
+                    let file = file_operation::available_child(&dir, "Untitled Document");
+                    file.create(gio::FileCreateFlags::NONE, None::<&gio::Cancellable>)
+                        .map(|_| (file.clone(), Operation::CreateFile(file)))
+                } else {
+                    let src = gio::File::for_path(&template);
+                    let name = src.basename().unwrap_or_default();
+                    let file = file_operation::available_child(&dir, &name.to_string_lossy());
+                    src.copy(
+                        &file,
+                        gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+                        None::<&gio::Cancellable>,
+                        None,
+                    )
+                    .map(|_| (file.clone(), Operation::Copy(vec![(src, file)])))
+                };
+
+                match created {
+                    Ok((file, operation)) => {
+                        app.record(operation);
+                        view.start_rename(&file);
+                    }
+                    Err(err) => app.toast(err.message()),
+                }
+            });
+
+            klass.install_action("duplicate-selection", None, |win, _name, _variant| {
+                let files = win.property::<BrowserView>("selected-page-child").selected_files();
+                win.property::<Application>("application").duplicate(&files);
+            });
+
+            // Takes whether the links point to their target with a relative path.
+            klass.install_action("make-link", Some("b"), |win, _name, variant| {
+                let relative = variant.and_then(|v| v.get::<bool>()).unwrap_or(false);
+                let files = win.property::<BrowserView>("selected-page-child").selected_files();
+                win.property::<Application>("application").make_links(&files, None, relative);
+            });
+
+            klass.install_action("new-folder-with-selection", None, |win, _name, _variant| {
+                let view = win.property::<BrowserView>("selected-page-child");
+                let files = view.selected_files();
+                if let (Some(dir), false) = (view.property::<Option<gio::File>>("dir"), files.is_empty()) {
+                    win.property::<Application>("application").new_folder_with(
+                        &dir,
+                        &files,
+                        clone!(@weak view => move |folder| view.start_rename(folder)),
+                    );
+                }
+            });
+
+            klass.install_action("restore-from-trash", None, |win, _name, _variant| {
+                let view = win.property::<BrowserView>("selected-page-child");
+                win.property::<Application>("application").restore(&view.selected_files());
+            });
+
+            klass.install_action("delete-permanently", None, |win, _name, _variant| {
+                let files = win.property::<BrowserView>("selected-page-child").selected_files();
+                if files.is_empty() {
+                    return;
+                }
+                gtk::glib::MainContext::default().spawn_local(clone!(@strong win => async move {
+                    if super::confirm_delete(&win, &files).await {
+                        win.property::<Application>("application").delete(files);
+                    }
+                }));
+            });
+
+            klass.install_action("empty-trash", None, |win, _name, _variant| {
+                gtk::glib::MainContext::default().spawn_local(clone!(@strong win => async move {
+                    let body = "All items in the trash will be deleted permanently.";
+                    if super::confirm(&win, "Empty Trash?", body, "Empty Trash").await {
+                        win.property::<Application>("application").empty_trash();
+                    }
+                }));
+            });
+
+            klass.install_action("open-trash", None, |win, _name, _variant| {
+                win.property::<BrowserView>("selected-page-child")
+                    .set_property("dir", crate::trash::location());
+            });
+
+            klass.install_action("cancel-cut", None, |win, _name, _variant| {
+                win.property::<Application>("application").cancel_cut();
+            });
+
             klass.install_action("open-in-default", None, |win, _name, _variant| {
                 if let Selection::Single(selection) = &*win.imp().selection.borrow() {
                     if let (Some(mime_type), Some(file)) = (
@@ -388,14 +945,43 @@ mod imp {
 
         #[template_callback(function = false)]
         fn search_entry_changed(&self, entry: &gtk::SearchEntry) {
-            dbg!("ASDasdasdasd");
-            if let Some(view) = self.selected_view.borrow().as_ref() {
-                if entry.text() != "" {
-                    view.search(entry.text().to_string());
-                } else {
-                    view.detach_search_view();
-                }
+            let view = match self.selected_view.borrow().as_ref() {
+                Some(view) => view.clone(),
+                None => return,
+            };
+            if entry.text() == "" {
+                view.detach_search_view();
+                return;
             }
+
+            // A query that doesn't make sense keeps the last results until it is fixed.
+            let query = match Query::parse(&entry.text()) {
+                Ok(query) => query,
+                Err(err) => {
+                    entry.add_css_class("error");
+                    entry.set_tooltip_text(Some(&err));
+                    return;
+                }
+            };
+            entry.remove_css_class("error");
+            entry.set_tooltip_text(None);
+
+            let mode = if self.content_search.is_active() {
+                search::Mode::Contents
+            } else {
+                search::Mode::Names
+            };
+            view.search(query, mode);
+        }
+
+        #[template_callback(function = false)]
+        fn search_mode_toggled(&self, button: &gtk::ToggleButton) {
+            self.search_entry.set_placeholder_text(Some(if button.is_active() {
+                "Search file contents"
+            } else {
+                "Search"
+            }));
+            self.search_entry_changed(&self.search_entry.get());
         }
 
         #[template_callback(function = false)]
@@ -441,13 +1027,108 @@ mod imp {
                 None::<&glib::Object>,
             );
 
-            if let Some(application) = self.application.borrow().as_ref() {
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "current-file",
+            )
+            .bind(&view.imp().file.get(), "label", None::<&glib::Object>);
+
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "status",
+            )
+            .bind(&view.imp().status.get(), "label", None::<&glib::Object>);
+
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "error-log",
+            )
+            .bind(&view.imp().error_log.get(), "label", None::<&glib::Object>);
+
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "has-error",
+            )
+            .bind(&view.imp().error_box.get(), "visible", None::<&glib::Object>);
+
+            for (btn, action) in [
+                (view.imp().retry_btn.get(), ErrorAction::Retry),
+                (view.imp().skip_btn.get(), ErrorAction::Skip),
+                (view.imp().skip_all_btn.get(), ErrorAction::SkipAll),
+                (view.imp().abort_btn.get(), ErrorAction::Abort),
+            ] {
+                btn.connect_clicked(clone!(@strong item => move |_| {
+                    if let Some(model) = item.item().and_then(|i| i.downcast::<ProgressInfoModel>().ok()) {
+                        model.resolve_error(action);
+                    }
+                }));
+            }
+
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "started",
+            )
+            .bind(&view.imp().pause_btn.get(), "visible", None::<&glib::Object>);
+
+            for btn in [view.imp().up_btn.get(), view.imp().start_btn.get()] {
+                gtk::PropertyExpression::new(
+                    ProgressInfoModel::static_type(),
+                    Some(&item_expr),
+                    "queued",
+                )
+                .bind(&btn, "visible", None::<&glib::Object>);
+            }
+
+            view.imp()
+                .start_btn
+                .connect_clicked(clone!(@strong item => move |_| {
+                    if let Some(model) = item.item().and_then(|i| i.downcast::<ProgressInfoModel>().ok()) {
+                        model.start();
+                    }
+                }));
+
+            gtk::PropertyExpression::new(
+                ProgressInfoModel::static_type(),
+                Some(&item_expr),
+                "paused",
+            )
+            .bind(&view.imp().pause_btn.get(), "active", None::<&glib::Object>);
+
+            view.imp()
+                .pause_btn
+                .connect_toggled(clone!(@strong item => move |btn| {
+                    if let Some(model) = item.item().and_then(|i| i.downcast::<ProgressInfoModel>().ok()) {
+                        model.set_paused(btn.is_active());
+                    }
+                }));
+
+            // `self.application` is never filled in, go through the default application instead.
+            if let Some(application) = gio::Application::default()
+                .and_then(|app| app.downcast::<Application>().ok())
+            {
                 let store = application.imp().0 .1.clone();
 
+                view.imp()
+                    .up_btn
+                    .connect_clicked(clone!(@strong item, @weak application => move |_| {
+                        if let Some(model) = item.item().and_then(|i| i.downcast::<ProgressInfoModel>().ok()) {
+                            application.move_job_up(&model);
+                        }
+                    }));
+
+                // Cancels a running job, a job that is over is dismissed instead.
                 view.imp()
                     .cancel_btn
                     .connect_clicked(clone!(@strong item => move |_| {
-                        store.remove(item.position());
+                        match item.item().and_then(|i| i.downcast::<ProgressInfoModel>().ok()) {
+                            Some(model) if model.is_active() => model.cancel(),
+                            _ => store.remove(item.position()),
+                        }
                     }));
             }
 
@@ -457,16 +1138,32 @@ mod imp {
 
     impl ObjectImpl for Window {
         fn constructed(&self, obj: &Self::Type) {
+            self.search_filters
+                .set_popover(Some(&query::chips_popover(&self.search_entry.get())));
+
+            // The tab bar switches to a tab hovered during a drag by itself.
+            self.tabbar.setup_extra_drop_target(crate::dnd::ACTIONS, &crate::dnd::types());
+            self.tabbar.connect_extra_drag_drop(|_, page, value| {
+                let files = crate::dnd::files(value);
+                let dir = page
+                    .child()
+                    .downcast::<BrowserView>()
+                    .ok()
+                    .and_then(|view| view.property::<Option<gio::File>>("dir"));
+                match dir {
+                    Some(dir) if crate::dnd::accepts(&files, &dir) => {
+                        let action = crate::dnd::action(crate::dnd::current_modifiers());
+                        crate::dnd::perform(files, &dir, action)
+                    }
+                    _ => false,
+                }
+            });
+
             let gesture = gtk::GestureClick::new();
             self.view_port.add_controller(&gesture);
 
             gesture.set_button(3);
             gesture.connect_released(clone!(@strong obj, @strong self.popover as popover, @strong self.selection as selection => move |gesture, _n_press, x, y| {
-                let application = obj.application().and_then(|app| app.downcast::<Application>().ok()).unwrap();
-
-
-
-                let selected_model: gio::ListStore = application.property("selected-items-store");
                 let selection_model = obj.property::<gtk::MultiSelection>("selection-model");
                 let filter_model = gtk::SelectionFilterModel::new(Some(&selection_model));
 
@@ -488,6 +1185,7 @@ mod imp {
                     Selection::None
                 });
 
+                let is_multi = matches!(&*selection.borrow(), Selection::Multi(_));
                 let is_single_dir = single.as_ref().map(|s| s.file_type() == gio::FileType::Directory).unwrap_or(false);
 
                 let menu = gio::Menu::new();
@@ -508,14 +1206,46 @@ mod imp {
 
 
                     section3.append(Some("New Folder"), Some("new-folder"));
+                    if single.is_none() {
+                        section3.append_submenu(Some("New Document"), &obj.templates_menu());
+                    }
                     section1.append(Some("Open Terminal Here"), Some("open-in-terminal"));
                 }
 
+                let section4 = gio::Menu::new();
+                let in_trash = obj.property::<BrowserView>("selected-page-child").is_trash();
+
                 if single.is_some() {
+                    section2.append(Some("Cut"), Some("cut"));
                     section2.append(Some("Copy"), Some("copy"));
+                    section2.append(Some("Rename…"), Some("rename"));
+
+                    if in_trash {
+                        section4.append(Some("Restore"), Some("restore-from-trash"));
+                        section4.append(Some("Delete Permanently"), Some("delete-permanently"));
+                    } else {
+                        section3.append(Some("Duplicate"), Some("duplicate-selection"));
+                        let link = gio::MenuItem::new(Some("Make Link"), None);
+                        link.set_action_and_target_value(Some("make-link"), Some(&false.to_variant()));
+                        section3.append_item(&link);
+                        let link = gio::MenuItem::new(Some("Make Relative Link"), None);
+                        link.set_action_and_target_value(Some("make-link"), Some(&true.to_variant()));
+                        section3.append_item(&link);
+
+                        section4.append(Some("Move to Trash"), Some("trash"));
+                        section4.append(Some("Delete Permanently…"), Some("delete-permanently"));
+                    }
+                }
+
+                if in_trash {
+                    section4.append(Some("Empty Trash"), Some("empty-trash"));
+                }
+
+                if is_multi && !in_trash {
+                    section3.append(Some("New Folder with Selection"), Some("new-folder-with-selection"));
                 }
 
-                if selected_model.n_items() > 0 {
+                if crate::clipboard::clipboard().map_or(false, |clipboard| crate::clipboard::has_files(&clipboard)) {
                     section2.append(Some("Paste"), Some("paste"));
                 }
 
@@ -527,6 +1257,7 @@ mod imp {
                 menu.append_section(None, &section1);
                 menu.append_section(None, &section2);
                 menu.append_section(None, &section3);
+                menu.append_section(None, &section4);
                 popover.set_menu_model(Some(&menu));
 
 